@@ -0,0 +1,132 @@
+use std::num::NonZeroU32;
+
+use bytes::Bytes;
+use eyre::eyre;
+use mp3lame_encoder::{
+    Bitrate, Builder, FlushGap, InterleavedPcm, Mode, MonoPcm, Quality, max_required_buffer_size,
+};
+
+use super::mp3::{Format, Layer};
+
+/// Bitrates supported by LAME in kbps
+const BITRATES: [(u32, Bitrate); 16] = [
+    (8, Bitrate::Kbps8),
+    (16, Bitrate::Kbps16),
+    (24, Bitrate::Kbps24),
+    (32, Bitrate::Kbps32),
+    (40, Bitrate::Kbps40),
+    (48, Bitrate::Kbps48),
+    (64, Bitrate::Kbps64),
+    (80, Bitrate::Kbps80),
+    (96, Bitrate::Kbps96),
+    (112, Bitrate::Kbps112),
+    (128, Bitrate::Kbps128),
+    (160, Bitrate::Kbps160),
+    (192, Bitrate::Kbps192),
+    (224, Bitrate::Kbps224),
+    (256, Bitrate::Kbps256),
+    (320, Bitrate::Kbps320),
+];
+
+/// Encode interleaved samples with LAME as MPEG audio Layer III of `format`
+/// The bitrate is the lowest supported one of at least `bitrate` kbps. The first frame is an
+/// Info frame whose LAME tag holds the encoder delay and padding, which gapless players (and
+/// `FrameJoiner`) remove, so the encoded audio plays exactly the given samples.
+pub fn encode(samples: &[f32], format: Format, bitrate: u32) -> eyre::Result<Bytes> {
+    if format.layer != Layer::Layer3 {
+        return Err(eyre!("Audio can't be encoded as {format}"));
+    }
+
+    let bitrate = BITRATES
+        .iter()
+        .find(|(rate, _)| *rate >= bitrate)
+        .map_or(Bitrate::Kbps320, |(_, bitrate)| *bitrate);
+    let mode = if format.mono {
+        Mode::Mono
+    } else {
+        Mode::JointStereo
+    };
+
+    let mut builder = Builder::new().ok_or(eyre!("Unable to create an MP3 encoder"))?;
+    let configure = |e| eyre!("Unable to configure the MP3 encoder for {format}: {e}");
+    builder
+        .set_num_channels(format.channels() as u8)
+        .map_err(configure)?;
+    builder
+        .set_sample_rate(format.sample_rate)
+        .map_err(configure)?;
+    builder
+        .set_output_sample_rate(NonZeroU32::new(format.sample_rate))
+        .map_err(configure)?;
+    builder.set_brate(bitrate).map_err(configure)?;
+    builder.set_mode(mode).map_err(configure)?;
+    builder.set_quality(Quality::NearBest).map_err(configure)?;
+    builder.set_to_write_vbr_tag(true).map_err(configure)?;
+    let mut encoder = builder.build().map_err(configure)?;
+
+    let mut audio = Vec::with_capacity(max_required_buffer_size(samples.len() / format.channels()));
+    let encoded = if format.mono {
+        encoder.encode_to_vec(MonoPcm(samples), &mut audio)
+    } else {
+        encoder.encode_to_vec(InterleavedPcm(samples), &mut audio)
+    };
+    encoded.map_err(|e| eyre!("Unable to encode audio: {e}"))?;
+
+    // Remaining samples fill at most a few frames
+    audio.reserve(7200);
+    encoder
+        .flush_to_vec::<FlushGap>(&mut audio)
+        .map_err(|e| eyre!("Unable to encode audio: {e}"))?;
+
+    // LAME starts with a placeholder of the Info frame, which is complete once all audio is
+    // encoded
+    let mut tag = Vec::with_capacity(encoder.lame_tag_size());
+    if encoder.lame_tag_encode_to_vec(&mut tag).is_some() && tag.len() <= audio.len() {
+        audio[..tag.len()].copy_from_slice(&tag);
+    }
+
+    Ok(audio.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::mp3::{self, Version};
+
+    #[test]
+    fn writes_lame_tags() {
+        let format = Format {
+            version: Version::Mpeg2,
+            layer: Layer::Layer3,
+            sample_rate: 24000,
+            mono: true,
+        };
+        let samples: Vec<f32> = (0..24000)
+            .map(|i| 0.5 * (i as f32 * 2.0 * std::f32::consts::PI / 48.0).sin())
+            .collect();
+        let audio = encode(&samples, format, 56).unwrap();
+
+        let frames: Vec<_> = mp3::frames(&audio).collect();
+        assert!(frames.iter().all(|frame| frame.header.format() == format));
+        assert!(frames.iter().all(|frame| frame.header.bitrate == 64));
+
+        // The delay and padding of the tag fill the other frames up to the encoded samples
+        let (delay, padding) = frames[0].info_tag().unwrap();
+        assert!(frames[1..].iter().all(|frame| frame.info_tag().is_none()));
+        assert_eq!(
+            (delay + samples.len() as u32 + padding) as usize,
+            (frames.len() - 1) * 576
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_formats() {
+        let format = Format {
+            version: Version::Mpeg1,
+            layer: Layer::Layer2,
+            sample_rate: 44100,
+            mono: false,
+        };
+        assert!(encode(&[0.0; 2304], format, 128).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+/// Highest absolute sample value after changing the loudness (-1 dBFS)
+const CEILING: f64 = 0.891;
+
+/// Time in which the gain is reduced before a peak (seconds)
+const ATTACK: f64 = 0.005;
+
+/// Time in which the gain recovers after a peak (seconds)
+const RELEASE: f64 = 0.1;
+
+/// Coefficient of a one pole smoothing filter with a time constant in seconds
+fn smoothing(time: f64, sample_rate: u32) -> f64 {
+    1.0 - (-1.0 / (time * sample_rate as f64)).exp()
+}
+
+/// Change the loudness of interleaved samples by `gain_db`
+/// Peaks which would exceed the ceiling are limited, the gain is reduced smoothly shortly before
+/// them and recovers afterwards (all channels are changed by the same gain)
+pub fn apply_gain(samples: &mut [f32], channels: usize, sample_rate: u32, gain_db: f64) {
+    let gain = 10f64.powf(gain_db / 20.0);
+
+    // Highest gain of every sample frame which keeps it below the ceiling
+    let mut gains: Vec<f64> = samples
+        .chunks_exact(channels)
+        .map(|frame| {
+            let peak = frame
+                .iter()
+                .fold(0.0f64, |peak, sample| peak.max(sample.abs() as f64));
+            if peak * gain > CEILING {
+                CEILING / peak
+            } else {
+                gain
+            }
+        })
+        .collect();
+
+    // The gain may only be lower than required, going backwards it is reduced ahead of peaks and
+    // going forward it recovers after them
+    for (coefficient, backwards) in [
+        (smoothing(ATTACK, sample_rate), true),
+        (smoothing(RELEASE, sample_rate), false),
+    ] {
+        let mut current = gain;
+        let mut smooth = |required: &mut f64| {
+            current = required.min(current + (gain - current) * coefficient);
+            *required = current;
+        };
+        if backwards {
+            gains.iter_mut().rev().for_each(&mut smooth);
+        } else {
+            gains.iter_mut().for_each(&mut smooth);
+        }
+    }
+
+    for (frame, gain) in samples.chunks_exact_mut(channels).zip(gains) {
+        for sample in frame {
+            *sample = (*sample as f64 * gain) as f32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(amplitude: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| amplitude * (i as f32 * 2.0 * std::f32::consts::PI / 48.0).sin())
+            .collect()
+    }
+
+    #[test]
+    fn applies_gain_exactly_below_ceiling() {
+        let original = sine(0.1, 4800);
+        let mut samples = original.clone();
+        apply_gain(&mut samples, 1, 48000, 12.0);
+
+        let factor = 10f32.powf(12.0 / 20.0);
+        for (sample, original) in samples.iter().zip(&original) {
+            assert!((sample - original * factor).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn limits_peaks() {
+        // A loud burst in a quiet stereo signal
+        let mut samples = sine(0.05, 192000);
+        for sample in &mut samples[48000..48480] {
+            *sample *= 16.0;
+        }
+        let original = samples.clone();
+        apply_gain(&mut samples, 2, 48000, 20.0);
+
+        let peak = samples
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!(peak <= CEILING as f32 + 1e-6);
+        assert!(peak > CEILING as f32 * 0.99);
+
+        // Far from the burst the gain isn't reduced
+        assert!((samples[1000] - original[1000] * 10.0).abs() < 1e-5);
+        assert!((samples[190000] - original[190000] * 10.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,133 @@
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// Maximum number of chapters listed in the table of contents (entry count is a single byte)
+const MAX_CHAPTERS: usize = 255;
+
+/// Chapter of the audio (times in milliseconds)
+pub struct ChapterFrame<'a> {
+    pub title: &'a str,
+    pub start: u32,
+    pub end: u32,
+}
+
+/// ID3v2.3 tag which is prepended to MP3 audio
+#[derive(Clone, Default)]
+pub struct Tag {
+    frames: BytesMut,
+}
+
+/// Text encoded as UTF-16 with byte order mark (encoding `1`), including the terminator
+fn utf16(text: &str) -> Vec<u8> {
+    let mut encoded = vec![0xff, 0xfe];
+    for unit in text.encode_utf16() {
+        encoded.extend_from_slice(&unit.to_le_bytes());
+    }
+    encoded.extend_from_slice(&[0, 0]);
+
+    encoded
+}
+
+/// Frame with the given ID and content
+fn frame(id: &str, content: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(10 + content.len());
+    frame.extend_from_slice(id.as_bytes());
+    frame.extend_from_slice(&(content.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&[0, 0]);
+    frame.extend_from_slice(content);
+
+    frame
+}
+
+/// Text information frame (e.g. `TIT2` for the title)
+fn text_frame(id: &str, text: &str) -> Vec<u8> {
+    let mut content = vec![1];
+    content.extend(utf16(text));
+
+    frame(id, &content)
+}
+
+impl Tag {
+    /// Whether the tag has no frames
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Add a text information frame (e.g. `TIT2` for the title), empty text is skipped
+    pub fn text(&mut self, id: &str, text: &str) {
+        if !text.trim().is_empty() {
+            self.frames.extend(text_frame(id, text.trim()));
+        }
+    }
+
+    /// Add a user defined text frame (`TXXX`)
+    pub fn user_text(&mut self, description: &str, value: &str) {
+        let mut content = vec![1];
+        content.extend(utf16(description));
+        content.extend(utf16(value));
+
+        self.frames.extend(frame("TXXX", &content));
+    }
+
+    /// Add a URL link frame (e.g. `WOAS` for the source webpage)
+    /// URLs are ISO-8859-1 encoded, so only ASCII URLs are written
+    pub fn url(&mut self, id: &str, url: &str) {
+        if url.is_ascii() && !url.is_empty() {
+            self.frames.extend(frame(id, url.as_bytes()));
+        }
+    }
+
+    /// Add the front cover (`APIC`) with the given MIME type
+    pub fn picture(&mut self, mime_type: &str, data: &[u8]) {
+        let mut content = vec![0];
+        content.extend_from_slice(mime_type.as_bytes());
+        // MIME type terminator, picture type (front cover) and empty description
+        content.extend_from_slice(&[0, 0x03, 0]);
+        content.extend_from_slice(data);
+
+        self.frames.extend(frame("APIC", &content));
+    }
+
+    /// Add chapter frames (`CHAP`) and their table of contents (`CTOC`)
+    pub fn chapters(&mut self, chapters: &[ChapterFrame]) {
+        let chapters = &chapters[..chapters.len().min(MAX_CHAPTERS)];
+        if chapters.is_empty() {
+            return;
+        }
+
+        // Top-level (0x02) and ordered (0x01) table of contents
+        let mut toc = b"toc\0".to_vec();
+        toc.push(0x03);
+        toc.push(chapters.len() as u8);
+        for nr in 0..chapters.len() {
+            toc.extend_from_slice(format!("chp{nr}\0").as_bytes());
+        }
+        self.frames.extend(frame("CTOC", &toc));
+
+        for (nr, chapter) in chapters.iter().enumerate() {
+            let mut chap = format!("chp{nr}\0").into_bytes();
+            chap.extend_from_slice(&chapter.start.to_be_bytes());
+            chap.extend_from_slice(&chapter.end.to_be_bytes());
+            // Byte offsets are unused
+            chap.extend_from_slice(&[0xff; 8]);
+            chap.extend(text_frame("TIT2", chapter.title));
+            self.frames.extend(frame("CHAP", &chap));
+        }
+    }
+
+    /// Encoded tag (header and frames)
+    pub fn to_bytes(&self) -> Bytes {
+        let size = self.frames.len() as u32;
+        // Tag size is a synchsafe integer (7 bits per byte)
+        let synchsafe = (size & 0x7f)
+            | ((size & 0x3f80) << 1)
+            | ((size & 0x1f_c000) << 2)
+            | ((size & 0x0fe0_0000) << 3);
+
+        let mut tag = BytesMut::with_capacity(10 + self.frames.len());
+        tag.put_slice(b"ID3\x03\x00\x00");
+        tag.put_u32(synchsafe);
+        tag.put_slice(&self.frames);
+
+        tag.freeze()
+    }
+}
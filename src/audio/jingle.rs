@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use bytes::Bytes;
+use eyre::{Context, eyre};
+
+use super::mp3::{Format, FrameJoiner};
+use crate::config::JingleConfig;
+
+/// MPEG audio which is joined with the narrated article
+pub struct Jingle {
+    /// Audio frames (without tags)
+    pub frames: Bytes,
+
+    pub format: Format,
+
+    /// Encoder delay and padding remaining in the frames (see `FrameJoiner::gapless`)
+    pub gapless: Option<(u32, u32)>,
+}
+
+impl Jingle {
+    /// Read an MP3 file
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        let content =
+            std::fs::read(path).wrap_err_with(|| format!("Unable to read {}", path.display()))?;
+
+        let mut joiner = FrameJoiner::default();
+        let frames = joiner.push(&content);
+        let format = joiner
+            .format()
+            .ok_or_else(|| eyre!("{} contains no MPEG audio", path.display()))?;
+
+        Ok(Self {
+            frames,
+            format,
+            gapless: joiner.gapless(),
+        })
+    }
+}
+
+/// Jingles of a feed
+#[derive(Default)]
+pub struct Jingles {
+    pub intro: Option<Jingle>,
+    pub outro: Option<Jingle>,
+    pub stinger: Option<Jingle>,
+}
+
+impl Jingles {
+    /// Read the configured files
+    pub fn load(config: &JingleConfig) -> eyre::Result<Self> {
+        let load = |path: &Option<_>| path.as_deref().map(Jingle::load).transpose();
+
+        Ok(Self {
+            intro: load(&config.intro)?,
+            outro: load(&config.outro)?,
+            stinger: load(&config.stinger)?,
+        })
+    }
+}
@@ -0,0 +1,142 @@
+use std::f64::consts::PI;
+
+/// Blocks below this loudness are ignored (LUFS)
+const ABSOLUTE_GATE: f64 = -70.0;
+
+/// Blocks quieter than the loudness of the remaining blocks minus this are ignored (LU)
+const RELATIVE_GATE: f64 = -10.0;
+
+/// Length of the measured blocks and the step between them in seconds
+const BLOCK: f64 = 0.4;
+const BLOCK_STEP: f64 = 0.1;
+
+/// Biquad filter (transposed direct form II)
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+
+        y
+    }
+}
+
+/// K-weighting filter of ITU-R BS.1770 (high shelf and high pass) for the given sample rate
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = sample_rate as f64;
+
+    let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        state: [0.0; 2],
+    };
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        state: [0.0; 2],
+    };
+
+    [shelf, high_pass]
+}
+
+fn loudness(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+/// Integrated loudness (LUFS) of interleaved mono or stereo samples as defined by EBU R128
+/// Returns `None` if the audio is too short or silent
+pub fn integrated_loudness(samples: &[f32], channels: usize, sample_rate: u32) -> Option<f64> {
+    let mut filters = vec![k_weighting(sample_rate); channels];
+
+    // Sum of the squared weighted samples of all channels up to each sample
+    let mut energy = Vec::with_capacity(samples.len() / channels + 1);
+    energy.push(0.0);
+    for frame in samples.chunks_exact(channels) {
+        let power: f64 = frame
+            .iter()
+            .zip(&mut filters)
+            .map(|(sample, [shelf, high_pass])| {
+                let weighted = high_pass.process(shelf.process(*sample as f64));
+                weighted * weighted
+            })
+            .sum();
+        energy.push(energy.last().unwrap_or(&0.0) + power);
+    }
+
+    let block = (BLOCK * sample_rate as f64) as usize;
+    let step = (BLOCK_STEP * sample_rate as f64) as usize;
+    let blocks: Vec<f64> = (block..energy.len())
+        .step_by(step)
+        .map(|end| (energy[end] - energy[end - block]) / block as f64)
+        .filter(|mean_square| loudness(*mean_square) > ABSOLUTE_GATE)
+        .collect();
+    if blocks.is_empty() {
+        return None;
+    }
+
+    let threshold = loudness(blocks.iter().sum::<f64>() / blocks.len() as f64) + RELATIVE_GATE;
+    let gated: Vec<f64> = blocks
+        .into_iter()
+        .filter(|mean_square| loudness(*mean_square) > threshold)
+        .collect();
+
+    Some(loudness(gated.iter().sum::<f64>() / gated.len() as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1 kHz sine with an amplitude of 0.1 in every channel
+    fn sine(channels: usize, seconds: f64) -> Vec<f32> {
+        (0..(seconds * 48000.0) as usize)
+            .flat_map(|i| {
+                let sample = 0.1 * (2.0 * PI * 1000.0 * i as f64 / 48000.0).sin();
+                vec![sample as f32; channels]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn measures_sine_loudness() {
+        let mono = integrated_loudness(&sine(1, 5.0), 1, 48000).unwrap();
+        assert!((mono - -23.0).abs() < 0.1, "{mono}");
+
+        // Channels are summed
+        let stereo = integrated_loudness(&sine(2, 5.0), 2, 48000).unwrap();
+        assert!((stereo - -20.0).abs() < 0.1, "{stereo}");
+    }
+
+    #[test]
+    fn ignores_silence() {
+        assert_eq!(integrated_loudness(&[0.0; 96000], 1, 48000), None);
+        assert_eq!(integrated_loudness(&sine(1, 0.3), 1, 48000), None);
+
+        // Silent blocks between the speech are gated (only blocks overlapping it count)
+        let mut gapped = sine(1, 2.0);
+        gapped.extend(vec![0.0; 96000]);
+        gapped.extend(sine(1, 2.0));
+        let loudness = integrated_loudness(&gapped, 1, 48000).unwrap();
+        assert!((loudness - -23.0).abs() < 0.5, "{loudness}");
+    }
+}
@@ -0,0 +1,7 @@
+pub mod encoder;
+pub mod gain;
+pub mod id3;
+pub mod jingle;
+pub mod loudness;
+pub mod mp3;
+pub mod process;
@@ -0,0 +1,590 @@
+use std::{collections::VecDeque, fmt};
+
+use bytes::{Bytes, BytesMut};
+
+/// Bitrates in kbps by bitrate index (MPEG 1 Layer I, II, III and MPEG 2/2.5 Layer I, II/III)
+const BITRATES: [[u32; 15]; 5] = [
+    [
+        0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+    ],
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+    ],
+    [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ],
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+    ],
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+];
+
+/// Sample rates in Hz by sample rate index (MPEG 1)
+const SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+/// Delay of Layer III decoders (in samples) which follows the encoder delay of LAME tags
+pub const DECODER_DELAY: u32 = 529;
+
+/// Version of the MPEG standard
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Version {
+    Mpeg1,
+    Mpeg2,
+    Mpeg25,
+}
+
+/// MPEG audio layer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layer {
+    Layer1,
+    Layer2,
+    Layer3,
+}
+
+/// Format of MPEG audio, only audio of the same format can be joined
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Format {
+    pub version: Version,
+    pub layer: Layer,
+
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+
+    /// Whether the audio has a single channel
+    pub mono: bool,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {:?}, {} Hz, {}",
+            self.version,
+            self.layer,
+            self.sample_rate,
+            if self.mono { "mono" } else { "stereo" }
+        )
+    }
+}
+
+impl Format {
+    pub fn channels(&self) -> usize {
+        if self.mono { 1 } else { 2 }
+    }
+}
+
+/// Header of an MPEG audio frame
+#[derive(Clone, Copy, Debug)]
+pub struct FrameHeader {
+    pub version: Version,
+    pub layer: Layer,
+
+    /// Bitrate in kbps
+    pub bitrate: u32,
+
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+
+    /// Whether the frame contains an additional padding slot
+    pub padding: bool,
+
+    /// Whether the audio has a single channel
+    pub mono: bool,
+
+    /// Whether a CRC follows the header
+    pub protected: bool,
+}
+
+impl FrameHeader {
+    /// Parse the 4 byte header at the start of `bytes`
+    /// Returns `None` if it isn't a valid header (free format frames are not supported)
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let header = u32::from_be_bytes(bytes.get(..4)?.try_into().ok()?);
+
+        if header >> 21 != 0x7ff {
+            return None;
+        }
+
+        let version = match (header >> 19) & 0b11 {
+            0b00 => Version::Mpeg25,
+            0b10 => Version::Mpeg2,
+            0b11 => Version::Mpeg1,
+            _ => return None,
+        };
+        let layer = match (header >> 17) & 0b11 {
+            0b01 => Layer::Layer3,
+            0b10 => Layer::Layer2,
+            0b11 => Layer::Layer1,
+            _ => return None,
+        };
+
+        let bitrate_index = ((header >> 12) & 0b1111) as usize;
+        let sample_rate_index = ((header >> 10) & 0b11) as usize;
+        if bitrate_index == 0 || bitrate_index == 15 || sample_rate_index == 3 {
+            return None;
+        }
+
+        let bitrates = match (version, layer) {
+            (Version::Mpeg1, Layer::Layer1) => &BITRATES[0],
+            (Version::Mpeg1, Layer::Layer2) => &BITRATES[1],
+            (Version::Mpeg1, Layer::Layer3) => &BITRATES[2],
+            (_, Layer::Layer1) => &BITRATES[3],
+            (_, _) => &BITRATES[4],
+        };
+        let sample_rate = match version {
+            Version::Mpeg1 => SAMPLE_RATES[sample_rate_index],
+            Version::Mpeg2 => SAMPLE_RATES[sample_rate_index] / 2,
+            Version::Mpeg25 => SAMPLE_RATES[sample_rate_index] / 4,
+        };
+
+        Some(Self {
+            version,
+            layer,
+            bitrate: bitrates[bitrate_index],
+            sample_rate,
+            padding: (header >> 9) & 1 == 1,
+            mono: (header >> 6) & 0b11 == 0b11,
+            protected: (header >> 16) & 1 == 0,
+        })
+    }
+
+    pub fn format(&self) -> Format {
+        Format {
+            version: self.version,
+            layer: self.layer,
+            sample_rate: self.sample_rate,
+            mono: self.mono,
+        }
+    }
+
+    /// Length of the frame in bytes (including the header)
+    pub fn frame_len(&self) -> usize {
+        let bitrate = self.bitrate as usize * 1000;
+        let sample_rate = self.sample_rate as usize;
+        let padding = self.padding as usize;
+
+        match (self.layer, self.version) {
+            (Layer::Layer1, _) => (12 * bitrate / sample_rate + padding) * 4,
+            (Layer::Layer3, Version::Mpeg2 | Version::Mpeg25) => {
+                72 * bitrate / sample_rate + padding
+            }
+            (_, _) => 144 * bitrate / sample_rate + padding,
+        }
+    }
+
+    /// Number of samples (per channel) in the frame
+    pub fn samples(&self) -> u32 {
+        match (self.layer, self.version) {
+            (Layer::Layer1, _) => 384,
+            (Layer::Layer3, Version::Mpeg2 | Version::Mpeg25) => 576,
+            (_, _) => 1152,
+        }
+    }
+
+    /// Position of the Layer III side information in the frame
+    pub fn side_info_start(&self) -> usize {
+        if self.protected { 6 } else { 4 }
+    }
+
+    /// Length of the Layer III side information following the header
+    pub fn side_info_len(&self) -> usize {
+        match (self.version, self.mono) {
+            (Version::Mpeg1, true) => 17,
+            (Version::Mpeg1, false) => 32,
+            (_, true) => 9,
+            (_, false) => 17,
+        }
+    }
+}
+
+/// Frame of MPEG audio
+pub struct Frame<'a> {
+    pub header: FrameHeader,
+
+    /// Complete frame (including the header)
+    pub data: &'a [u8],
+}
+
+impl Frame<'_> {
+    /// Encoder delay and padding (in samples) if this is a Xing/Info frame
+    /// Such frames only contain metadata of the stream and no audio
+    pub fn info_tag(&self) -> Option<(u32, u32)> {
+        let start = self.header.side_info_start() + self.header.side_info_len();
+        let tag = self.data.get(start..start + 8)?;
+        if &tag[..4] != b"Xing" && &tag[..4] != b"Info" {
+            return None;
+        }
+
+        // Optional fields (frames, bytes, table of contents, quality) precede the LAME tag
+        let flags = u32::from_be_bytes(tag[4..8].try_into().ok()?);
+        let lame = start
+            + 8
+            + [(0b0001, 4), (0b0010, 4), (0b0100, 100), (0b1000, 4)]
+                .iter()
+                .filter(|(flag, _)| flags & flag != 0)
+                .map(|(_, len)| len)
+                .sum::<usize>();
+
+        // Delay and padding are stored as two 12 bit values at offset 21 of the LAME tag
+        let delay_padding = self
+            .data
+            .get(lame..lame + 24)
+            .filter(|lame_tag| lame_tag.starts_with(b"LAME") || lame_tag.starts_with(b"Lavc"))
+            .map(|lame_tag| {
+                let [a, b, c] = [lame_tag[21], lame_tag[22], lame_tag[23]].map(u32::from);
+                ((a << 4) | (b >> 4), ((b & 0x0f) << 8) | c)
+            });
+
+        Some(delay_padding.unwrap_or_default())
+    }
+
+    /// Whether the CRC of a protected frame matches the header and Layer III side information
+    /// (unprotected frames and other layers are not checked)
+    pub fn has_valid_crc(&self) -> bool {
+        if !self.header.protected || self.header.layer != Layer::Layer3 {
+            return true;
+        }
+
+        let end = 6 + self.header.side_info_len();
+        match (self.data.get(4..6), self.data.get(6..end)) {
+            (Some(&[high, low]), Some(side_info)) => {
+                crc16(&self.data[2..4], side_info) == u16::from_be_bytes([high, low])
+            }
+            _ => false,
+        }
+    }
+
+    /// Number of bytes of previous frames holding the start of the Layer III main data
+    /// (bit reservoir), frames starting with 0 don't depend on previous frames
+    pub fn main_data_begin(&self) -> u32 {
+        let start = self.header.side_info_start();
+        match (self.header.layer, self.data.get(start..start + 2)) {
+            (Layer::Layer3, Some(&[a, b])) => {
+                let bits = u32::from(u16::from_be_bytes([a, b]));
+                match self.header.version {
+                    Version::Mpeg1 => bits >> 7,
+                    _ => bits >> 8,
+                }
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// CRC-16 protecting MPEG audio frames (polynomial 0x8005) of the last two header bytes
+/// followed by the side information
+fn crc16(header: &[u8], side_info: &[u8]) -> u16 {
+    header
+        .iter()
+        .chain(side_info)
+        .fold(0xffff, |mut crc: u16, byte| {
+            crc ^= (*byte as u16) << 8;
+            for _ in 0..8 {
+                crc = if crc & 0x8000 != 0 {
+                    (crc << 1) ^ 0x8005
+                } else {
+                    crc << 1
+                };
+            }
+            crc
+        })
+}
+
+/// Frames of joined MPEG audio without tags (see `FrameJoiner`)
+pub fn frames(data: &[u8]) -> impl Iterator<Item = Frame<'_>> {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        let header = FrameHeader::parse(rest)?;
+        let (frame, next) = rest.split_at_checked(header.frame_len())?;
+        rest = next;
+
+        Some(Frame {
+            header,
+            data: frame,
+        })
+    })
+}
+
+/// Playback duration of joined MPEG audio in seconds (Info frames of LAME tags are skipped)
+pub fn duration(data: &[u8]) -> f64 {
+    frames(data)
+        .filter(|frame| frame.info_tag().is_none())
+        .map(|frame| frame.header.samples() as f64 / frame.header.sample_rate as f64)
+        .sum()
+}
+
+/// Length of an ID3v2 tag at the start of `bytes` (including header and footer)
+pub fn id3v2_len(bytes: &[u8]) -> Option<usize> {
+    let header = bytes
+        .get(..10)
+        .filter(|header| header.starts_with(b"ID3"))?;
+
+    // Tag size is a 28 bit synchsafe integer
+    let size = header[6..10]
+        .iter()
+        .fold(0usize, |size, byte| (size << 7) | (*byte & 0x7f) as usize);
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+
+    Some(10 + size + footer)
+}
+
+/// Joins MPEG audio of several files which is received in pieces
+/// Only complete audio frames are passed on, ID3 tags and Xing/Info frames (which would describe
+/// only one of the files) are dropped
+/// Frames which only contain the encoder delay or padding of a file (according to its LAME tag)
+/// are dropped as well, unless following frames use them as bit reservoir (less than a frame of
+/// delay and padding remains, trimming it exactly requires decoding, see `audio::process`)
+/// Decoders delay the audio by `DECODER_DELAY`, so the padding frame holding the end of the
+/// audio is kept
+#[derive(Default)]
+pub struct FrameJoiner {
+    pending: BytesMut,
+
+    /// Format of the first passed on frame
+    format: Option<Format>,
+
+    /// Encoder delay of the current file (in samples) which is not covered by `leading` yet
+    delay: u32,
+
+    /// Leading frames of the current file which only contain encoder delay
+    leading: Vec<(Bytes, FrameHeader)>,
+
+    /// Number of frames at the end of the current file which only contain encoder padding
+    padding_frames: usize,
+
+    /// Frames held back until it is known that they don't belong to the padding
+    trailing: VecDeque<(Bytes, FrameHeader)>,
+
+    /// Encoder delay and padding of the current file which remain after dropping frames
+    gapless: Option<(u32, u32)>,
+}
+
+impl FrameJoiner {
+    /// Add received data, returns the audio frames which are complete
+    pub fn push(&mut self, data: &[u8]) -> Bytes {
+        self.pending.extend_from_slice(data);
+
+        let mut frames = BytesMut::new();
+        let mut pos = 0;
+        while pos < self.pending.len() {
+            let rest = &self.pending[pos..];
+
+            // Wait for the rest of incomplete tags and headers
+            if rest.starts_with(b"ID3") {
+                match id3v2_len(rest) {
+                    Some(len) if len <= rest.len() => {
+                        pos += len;
+                        continue;
+                    }
+                    _ => break,
+                }
+            }
+            if rest.len() < 4 {
+                break;
+            }
+
+            let Some(header) = FrameHeader::parse(rest) else {
+                pos += 1;
+                continue;
+            };
+            if header.frame_len() > rest.len() {
+                break;
+            }
+
+            let data = Bytes::copy_from_slice(&rest[..header.frame_len()]);
+            pos += header.frame_len();
+
+            let frame = Frame {
+                header,
+                data: &data,
+            };
+            if let Some((delay, padding)) = frame.info_tag() {
+                self.delay = delay;
+                let padding_frames = padding.saturating_sub(DECODER_DELAY) / header.samples();
+                self.padding_frames = padding_frames as usize;
+                self.gapless = Some((delay, padding - padding_frames * header.samples()));
+                continue;
+            }
+            if self.delay >= header.samples() {
+                self.delay -= header.samples();
+                self.leading.push((data, header));
+                continue;
+            }
+
+            // Leading frames can only be dropped if the first audible frame doesn't refer to them
+            self.delay = 0;
+            let leading = std::mem::take(&mut self.leading);
+            if frame.main_data_begin() > 0 {
+                for (data, header) in leading {
+                    self.hold(data, header, &mut frames);
+                }
+            } else if let Some((delay, _)) = &mut self.gapless {
+                *delay -= leading
+                    .iter()
+                    .map(|(_, header)| header.samples())
+                    .sum::<u32>();
+            }
+            self.hold(data, header, &mut frames);
+        }
+
+        let _ = self.pending.split_to(pos);
+        frames.freeze()
+    }
+
+    /// Pass on a frame once it is known that it doesn't belong to the padding of the file
+    fn hold(&mut self, data: Bytes, header: FrameHeader, frames: &mut BytesMut) {
+        self.trailing.push_back((data, header));
+        while self.trailing.len() > self.padding_frames {
+            let Some((data, header)) = self.trailing.pop_front() else {
+                break;
+            };
+            self.format.get_or_insert(header.format());
+            frames.extend_from_slice(&data);
+        }
+    }
+
+    /// End of the current file (incomplete data and frames with encoder padding are discarded)
+    pub fn end_file(&mut self) {
+        self.pending.clear();
+        self.leading.clear();
+        self.trailing.clear();
+        self.delay = 0;
+        self.padding_frames = 0;
+        self.gapless = None;
+    }
+
+    /// Encoder delay and padding (in samples) of the current file which remain in the passed on
+    /// frames, `None` if the file has no LAME tag
+    pub fn gapless(&self) -> Option<(u32, u32)> {
+        self.gapless
+    }
+
+    /// Format of the passed on audio (`None` until a frame was passed on)
+    pub fn format(&self) -> Option<Format> {
+        self.format
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// MPEG 2 Layer III frame (24 kHz mono, 64 kbps) marked with `nr`
+    fn frame(nr: u8, main_data_begin: u8) -> Vec<u8> {
+        let mut frame = vec![0; 192];
+        frame[..4].copy_from_slice(&[0xff, 0xf3, 0x84, 0xc0]);
+        frame[4] = main_data_begin;
+        frame[100] = nr;
+        frame
+    }
+
+    /// Info frame with a LAME tag (no optional fields)
+    fn info_frame(delay: u32, padding: u32) -> Vec<u8> {
+        let mut frame = frame(0, 0);
+        frame[13..17].copy_from_slice(b"Info");
+        frame[21..25].copy_from_slice(b"LAME");
+        frame[42..45].copy_from_slice(&[
+            (delay >> 4) as u8,
+            ((delay & 0x0f) << 4 | padding >> 8) as u8,
+            padding as u8,
+        ]);
+        frame
+    }
+
+    fn numbers(frames: &[u8]) -> Vec<u8> {
+        super::frames(frames).map(|frame| frame.data[100]).collect()
+    }
+
+    #[test]
+    fn parses_headers() {
+        let header = FrameHeader::parse(&frame(1, 0)).unwrap();
+        assert_eq!(header.version, Version::Mpeg2);
+        assert_eq!(header.layer, Layer::Layer3);
+        assert_eq!((header.bitrate, header.sample_rate), (64, 24000));
+        assert!(header.mono && !header.protected);
+        assert_eq!((header.frame_len(), header.samples()), (192, 576));
+    }
+
+    #[test]
+    fn reads_info_tags() {
+        let info = info_frame(1105, 1200);
+        let frame = Frame {
+            header: FrameHeader::parse(&info).unwrap(),
+            data: &info,
+        };
+        assert_eq!(frame.info_tag(), Some((1105, 1200)));
+
+        let audio = self::frame(1, 7);
+        let frame = Frame {
+            header: FrameHeader::parse(&audio).unwrap(),
+            data: &audio,
+        };
+        assert_eq!(frame.info_tag(), None);
+        assert_eq!(frame.main_data_begin(), 7);
+    }
+
+    #[test]
+    fn checks_crcs() {
+        assert_eq!(crc16(b"12", b"3456789"), 0xaee7);
+
+        // Protected frame, the CRC follows the header
+        let mut protected = frame(1, 0);
+        protected[1] = 0xf2;
+        protected[6..15].copy_from_slice(&[0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0, 0x11]);
+        let [high, low] = crc16(&protected[2..4], &protected[6..15]).to_be_bytes();
+        protected[4..6].copy_from_slice(&[high, low]);
+
+        let header = FrameHeader::parse(&protected).unwrap();
+        assert!(header.protected);
+        let valid = Frame {
+            header,
+            data: &protected,
+        };
+        assert!(valid.has_valid_crc());
+
+        let mut corrupted = protected.clone();
+        corrupted[10] ^= 0x04;
+        let invalid = Frame {
+            header,
+            data: &corrupted,
+        };
+        assert!(!invalid.has_valid_crc());
+    }
+
+    #[test]
+    fn drops_delay_and_padding_frames() {
+        // One frame of delay and two frames of padding, the decoder delay moves audio into the
+        // first one
+        let mut file = info_frame(1105, 1200);
+        for nr in 1..=6 {
+            file.extend(frame(nr, 0));
+        }
+
+        let mut joiner = FrameJoiner::default();
+        let mut joined = joiner.push(&file[..500]).to_vec();
+        joined.extend(joiner.push(&file[500..]));
+        assert_eq!(joiner.gapless(), Some((1105 - 576, 1200 - 576)));
+        joiner.end_file();
+        assert_eq!(numbers(&joined), [2, 3, 4, 5]);
+        assert!((duration(&joined) - 4.0 * 0.024).abs() < 1e-9);
+
+        // Without LAME tag all frames are kept
+        let joined = joiner.push(&file[192..]);
+        joiner.end_file();
+        assert_eq!(numbers(&joined), [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn keeps_delay_frames_used_as_bit_reservoir() {
+        let mut file = info_frame(1105, 0);
+        file.extend(frame(1, 0));
+        file.extend(frame(2, 10));
+        file.extend(frame(3, 0));
+
+        let mut joiner = FrameJoiner::default();
+        let joined = joiner.push(&file);
+        assert_eq!(joiner.gapless(), Some((1105, 0)));
+        joiner.end_file();
+        assert_eq!(numbers(&joined), [1, 2, 3]);
+    }
+}
@@ -0,0 +1,240 @@
+use bytes::Bytes;
+use eyre::eyre;
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{CODEC_TYPE_MP3, CodecParameters, Decoder, DecoderOptions},
+    formats::Packet,
+};
+use symphonia::default::codecs::MpaDecoder;
+
+use super::{
+    encoder,
+    gain::apply_gain,
+    loudness::integrated_loudness,
+    mp3::{self, DECODER_DELAY, Format},
+};
+
+/// Samples below this level are silent (-50 dBFS)
+const SILENCE_THRESHOLD: f32 = 0.003_162;
+
+/// Silence kept before and after the speech when trimming (seconds)
+const KEPT_SILENCE: f64 = 0.25;
+
+/// Post-processing of synthesized audio
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PostProcessing {
+    /// Target loudness in LUFS (the loudness is kept if not set)
+    pub loudness: Option<f64>,
+
+    /// Whether leading and trailing silence is trimmed
+    pub trim_silence: bool,
+}
+
+/// Audio of an episode which was synthesized (or played) at once
+pub struct Segment {
+    /// Audio frames (see `mp3::FrameJoiner`)
+    pub frames: Bytes,
+
+    /// Encoder delay and padding remaining in the frames (see `FrameJoiner::gapless`)
+    pub gapless: Option<(u32, u32)>,
+
+    /// Whether the segment is synthesized speech, other audio (jingles) is only re-encoded
+    pub speech: bool,
+}
+
+/// Processed audio of an episode
+pub struct Processed {
+    pub audio: Bytes,
+
+    /// End of every segment in seconds of gapless playback (the LAME tag of the audio describes
+    /// the encoder delay and padding, which players skip)
+    pub ends: Vec<f64>,
+}
+
+/// Decode Layer III frames of one format to interleaved samples
+/// Frames with an invalid CRC or referring to missing frames (bit reservoir) are silent
+pub fn decode(frames: &[u8], format: Format) -> eyre::Result<Vec<f32>> {
+    let params = CodecParameters::new().for_codec(CODEC_TYPE_MP3).clone();
+    let mut decoder = MpaDecoder::try_new(&params, &DecoderOptions::default())?;
+    let channels = format.channels();
+
+    let mut samples = Vec::new();
+    for frame in mp3::frames(frames) {
+        if frame.header.format() != format {
+            return Err(eyre!(
+                "Audio of different formats ({format} and {}) can't be processed",
+                frame.header.format()
+            ));
+        }
+
+        let start = samples.len();
+        if frame.has_valid_crc()
+            && let Ok(decoded) = decoder.decode(&Packet::new_from_slice(0, 0, 0, frame.data))
+            && decoded.spec().channels.count() == channels
+        {
+            let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+            buffer.copy_interleaved_ref(decoded);
+            samples.extend_from_slice(buffer.samples());
+        }
+        samples.resize(start + frame.header.samples() as usize * channels, 0.0);
+    }
+
+    Ok(samples)
+}
+
+/// Remove the encoder delay and padding (see `Segment::gapless`) from decoded samples
+fn trim_gapless(samples: &mut Vec<f32>, gapless: Option<(u32, u32)>, channels: usize) {
+    let Some((delay, padding)) = gapless else {
+        return;
+    };
+
+    let start = ((delay + DECODER_DELAY) as usize * channels).min(samples.len());
+    let padding = padding.saturating_sub(DECODER_DELAY) as usize * channels;
+    samples.truncate(samples.len().saturating_sub(padding).max(start));
+    samples.drain(..start);
+}
+
+/// Remove leading and trailing silence except for `KEPT_SILENCE` (silent audio is kept)
+fn trim_silence(samples: &mut Vec<f32>, channels: usize, sample_rate: u32) {
+    let loud = |sample: &f32| sample.abs() >= SILENCE_THRESHOLD;
+    let (Some(first), Some(last)) = (
+        samples.iter().position(loud),
+        samples.iter().rposition(loud),
+    ) else {
+        return;
+    };
+
+    let margin = (KEPT_SILENCE * sample_rate as f64) as usize * channels;
+    let start = (first / channels * channels).saturating_sub(margin);
+    let end = ((last / channels + 1) * channels + margin).min(samples.len());
+    samples.truncate(end);
+    samples.drain(..start);
+}
+
+impl PostProcessing {
+    pub fn is_enabled(&self) -> bool {
+        self.loudness.is_some() || self.trim_silence
+    }
+
+    /// Process the audio of an episode, blocks until it is encoded again
+    /// Every segment is decoded and trimmed sample-accurately (encoder delay and padding, silence
+    /// of speech), the loudness of all speech is measured at once and changed by the same gain,
+    /// then the episode is encoded once with LAME in the format and bitrate of the speech (also
+    /// when only trimming, frames can't be cut sample-accurately without decoding them)
+    pub fn apply(&self, segments: &[&Segment]) -> eyre::Result<Processed> {
+        let first = segments
+            .iter()
+            .filter(|segment| segment.speech)
+            .find_map(|segment| mp3::frames(&segment.frames).next())
+            .ok_or(eyre!("No synthesized audio frames"))?
+            .header;
+        let format = first.format();
+        let channels = format.channels();
+
+        let mut decoded = Vec::with_capacity(segments.len());
+        for segment in segments {
+            let mut samples = decode(&segment.frames, format)?;
+            trim_gapless(&mut samples, segment.gapless, channels);
+            if segment.speech && self.trim_silence {
+                trim_silence(&mut samples, channels, format.sample_rate);
+            }
+            decoded.push(samples);
+        }
+
+        if let Some(target) = self.loudness {
+            let speech: Vec<f32> = segments
+                .iter()
+                .zip(&decoded)
+                .filter(|(segment, _)| segment.speech)
+                .flat_map(|(_, samples)| samples.iter().copied())
+                .collect();
+
+            if let Some(loudness) = integrated_loudness(&speech, channels, format.sample_rate) {
+                for (_, samples) in segments
+                    .iter()
+                    .zip(&mut decoded)
+                    .filter(|(segment, _)| segment.speech)
+                {
+                    apply_gain(samples, channels, format.sample_rate, target - loudness);
+                }
+            }
+        }
+
+        let mut samples = Vec::with_capacity(decoded.iter().map(Vec::len).sum());
+        let mut ends = Vec::with_capacity(decoded.len());
+        for segment in decoded {
+            samples.extend(segment);
+            ends.push((samples.len() / channels) as f64 / format.sample_rate as f64);
+        }
+
+        Ok(Processed {
+            audio: encoder::encode(&samples, format, first.bitrate)?,
+            ends,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::mp3::{FrameJoiner, Layer, Version};
+
+    const FORMAT: Format = Format {
+        version: Version::Mpeg2,
+        layer: Layer::Layer3,
+        sample_rate: 24000,
+        mono: true,
+    };
+
+    /// 1 kHz sine between silence (durations in seconds)
+    fn tone(amplitude: f32, silence: f64, duration: f64) -> Vec<f32> {
+        let [silence, duration] = [silence, duration].map(|seconds| (seconds * 24000.0) as usize);
+        let mut samples = vec![0.0; silence];
+        samples.extend(
+            (0..duration).map(|i| {
+                amplitude * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 24000.0).sin()
+            }),
+        );
+        samples.extend(vec![0.0; silence]);
+        samples
+    }
+
+    /// Segment encoded with LAME and joined like synthesized audio
+    fn segment(samples: &[f32], speech: bool) -> Segment {
+        let mut joiner = FrameJoiner::default();
+        let frames = joiner.push(&encoder::encode(samples, FORMAT, 64).unwrap());
+
+        Segment {
+            frames,
+            gapless: joiner.gapless(),
+            speech,
+        }
+    }
+
+    #[test]
+    fn processes_segments() {
+        let speech = segment(&tone(0.05, 0.5, 1.0), true);
+        let jingle = segment(&tone(0.5, 0.0, 0.2), false);
+        let processing = PostProcessing {
+            loudness: Some(-16.0),
+            trim_silence: true,
+        };
+        let Processed { audio, ends } = processing.apply(&[&speech, &jingle]).unwrap();
+
+        // Silence is trimmed to 0.25 seconds, the jingle is kept sample-accurately
+        assert_eq!(ends.len(), 2);
+        assert!((ends[0] - 1.5).abs() < 0.01, "{ends:?}");
+        assert!((ends[1] - ends[0] - 0.2).abs() < 1e-9, "{ends:?}");
+
+        // The LAME tag describes the processed samples
+        let mut joiner = FrameJoiner::default();
+        let frames = joiner.push(&audio);
+        let mut decoded = decode(&frames, FORMAT).unwrap();
+        trim_gapless(&mut decoded, joiner.gapless(), 1);
+        assert_eq!(decoded.len(), (ends[1] * 24000.0).round() as usize);
+
+        let speech = &decoded[..(ends[0] * 24000.0) as usize];
+        let loudness = integrated_loudness(speech, 1, 24000).unwrap();
+        assert!((loudness - -16.0).abs() < 0.5, "{loudness}");
+    }
+}
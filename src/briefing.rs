@@ -0,0 +1,128 @@
+use poem::{Error, Result};
+use reqwest::StatusCode;
+use rss::{Channel, Guid, Item};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    cache::get_feed_dir,
+    content::{Article, Script},
+    feed::fetch_channel,
+    transcript,
+};
+
+/// Number of items read if the request doesn't limit them
+pub const DEFAULT_HEADLINES: u32 = 10;
+
+/// Length of the version (hex characters of the hash of the narrated text)
+const VERSION_LENGTH: usize = 16;
+
+/// Identifies the briefings of a feed instead of the feed URL in cache keys, the cache index and
+/// transcript URLs (briefings don't count towards the quota of the feed)
+pub fn source_id(url: &str) -> Result<String> {
+    Ok(format!("briefing/{}", get_feed_dir(url)?))
+}
+
+/// Whether `version` has the format of the versions of briefings (see `Briefing::version`)
+pub fn is_version(version: &str) -> bool {
+    version.len() == VERSION_LENGTH
+        && version
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte))
+}
+
+/// GUID of a version of the briefing of a feed
+pub fn uid(version: &str) -> String {
+    format!("briefing/{version}")
+}
+
+/// Single episode reading the title and first sentence of the latest items of a feed
+pub struct Briefing {
+    /// The original feed
+    pub channel: Channel,
+
+    /// Episode (without enclosure), its GUID changes whenever the headlines change
+    pub episode: Item,
+
+    pub script: Script,
+
+    /// Hash of the narrated text
+    pub version: String,
+}
+
+/// Fetch a feed and prepare the briefing of its first `count` items, every item forms a chapter
+pub async fn fetch(url: &str, ignore: &[String], count: u32) -> Result<Briefing> {
+    let channel = fetch_channel(url).await?;
+    let items: Vec<&Item> = channel
+        .items()
+        .iter()
+        .filter(|item| item.title().is_some_and(|title| !title.trim().is_empty()))
+        .take(count as usize)
+        .collect();
+
+    let mut script = Script::default();
+    for item in &items {
+        let title = item.title().unwrap_or_default().trim();
+        let article = match item.description() {
+            Some(article) => Article::parse(article, ignore)?.text,
+            None => String::new(),
+        };
+        let summary = transcript::sentences(&article)
+            .first()
+            .map_or("", |sentence| sentence.trim());
+
+        let headline = if title.ends_with(['.', '!', '?', '…']) {
+            String::from(title)
+        } else {
+            format!("{title}.")
+        };
+        script.push_chapter(title, &format!("{headline}\n\n{summary}\n\n"));
+    }
+    if script.chunks.is_empty() {
+        return Err(Error::from_string(
+            "Feed has no items with title",
+            StatusCode::NOT_FOUND,
+        ));
+    }
+    script.intro(&format!("Headlines of {}.", channel.title().trim()));
+
+    let version =
+        format!("{:x}", Sha256::digest(script.text().as_bytes()))[..VERSION_LENGTH].to_string();
+    let episode = Item {
+        title: Some(format!("Headlines of {}", channel.title().trim())),
+        link: Some(channel.link().to_string()),
+        description: Some(
+            items
+                .iter()
+                .filter_map(|item| item.title())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+        guid: Some(Guid {
+            value: uid(&version),
+            permalink: false,
+        }),
+        pub_date: items.iter().find_map(|item| item.pub_date.clone()),
+        ..Default::default()
+    };
+
+    Ok(Briefing {
+        channel,
+        episode,
+        script,
+        version,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_versions() {
+        assert!(is_version("0123456789abcdef"));
+        assert!(!is_version("0123456789ABCDEF"));
+        assert!(!is_version("0123456789abcde"));
+        assert!(!is_version("../../feed/guid1"));
+        assert!(!is_version(""));
+    }
+}
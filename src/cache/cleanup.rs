@@ -0,0 +1,536 @@
+use std::{collections::BTreeSet, sync::Arc, time::Duration};
+
+use eyre::eyre;
+use poem_openapi::Object;
+use tokio::sync::Mutex;
+
+use super::{
+    index::{CacheEntry, CacheIndex, unix_now},
+    storage::AudioStorage,
+};
+use crate::{config::FeedQuota, metrics};
+
+/// Different methods to cleanup cache
+#[derive(Clone, Debug)]
+pub enum CleanupMethod {
+    /// Delete least recently served item if the cache (without pinned items) exceeds a given
+    /// storage size (in byte)
+    MaxStorage(u64),
+
+    /// Delete item if the date of creation exceeds a given date
+    MaxAge(Duration),
+
+    /// Delete least recently served item if the free space on the filesystem holding the cache is
+    /// below a given size (in byte)
+    MinFreeSpace(u64),
+
+    /// Delete the oldest items of a feed which exceed the quota of the feed
+    /// The most recent items within the quota are not removed by `MaxStorage` and `MinFreeSpace`
+    FeedQuota(FeedQuota),
+}
+
+/// Combination of cleanup methods which are applied together
+/// Methods run in the order: `MaxAge`, `FeedQuota`, `MaxStorage`, `MinFreeSpace`
+#[derive(Clone, Debug, Default)]
+pub struct CleanupPolicy {
+    methods: Vec<CleanupMethod>,
+
+    /// Held while cleaning up (shared by all clones), so runs plan with the results of previous
+    /// runs instead of deleting the same files
+    running: Arc<Mutex<()>>,
+}
+
+impl CleanupPolicy {
+    pub fn new(
+        max_storage: Option<u64>,
+        max_age: Option<Duration>,
+        min_free_space: Option<u64>,
+        feed_quotas: Vec<FeedQuota>,
+    ) -> Self {
+        Self {
+            methods: max_age
+                .map(CleanupMethod::MaxAge)
+                .into_iter()
+                .chain(feed_quotas.into_iter().map(CleanupMethod::FeedQuota))
+                .chain(max_storage.map(CleanupMethod::MaxStorage))
+                .chain(min_free_space.map(CleanupMethod::MinFreeSpace))
+                .collect(),
+            running: Arc::default(),
+        }
+    }
+
+    /// Whether no cleanup method is configured
+    pub fn is_empty(&self) -> bool {
+        self.methods.is_empty()
+    }
+}
+
+/// Summary of a cleanup run
+#[derive(Clone, Debug, Default, Object)]
+pub struct CleanupReport {
+    /// Number of removed files
+    pub files: usize,
+
+    /// Number of freed bytes
+    pub bytes: u64,
+
+    /// Number of removed empty directories
+    pub dirs: usize,
+
+    /// Removed files (relative to the cache directory)
+    pub paths: Vec<String>,
+}
+
+impl CleanupReport {
+    /// Delete the given entries (audio and transcripts) from the audio storage and the index
+    pub async fn delete(
+        index: &CacheIndex,
+        storage: &AudioStorage,
+        entries: &[CacheEntry],
+    ) -> Self {
+        let mut report = Self::default();
+
+        for entry in entries {
+            let removed = match storage.delete_episode(&entry.path).await {
+                Ok(dirs) => index.remove(&entry.path).map(|size| (size, dirs)),
+                Err(e) => Err(e),
+            };
+
+            match removed {
+                Ok((size, dirs)) => {
+                    tracing::info!("Removed file {:?}", entry.path);
+                    report.files += 1;
+                    report.bytes += size;
+                    report.dirs += dirs;
+                    report.paths.push(entry.path.clone());
+                }
+                Err(e) => tracing::error!("{e}"),
+            }
+        }
+
+        report
+    }
+
+    /// Report which entries would be deleted without deleting them
+    fn preview(entries: &[CacheEntry]) -> Self {
+        Self {
+            files: entries.len(),
+            bytes: entries.iter().map(CacheEntry::stored_size).sum(),
+            dirs: 0,
+            paths: entries.iter().map(|entry| entry.path.clone()).collect(),
+        }
+    }
+}
+
+/// Select the least recently served entries until the given amount of bytes is reached
+fn least_recently_used(entries: &[CacheEntry], to_free: u64) -> Vec<CacheEntry> {
+    let mut entries = entries.to_vec();
+
+    // Sort files by last access (least recent first)
+    entries.sort_by_key(|entry| entry.last_access);
+
+    let mut selected = Vec::new();
+    let mut freed = 0;
+    for entry in entries {
+        if freed >= to_free {
+            break;
+        }
+
+        freed += entry.stored_size();
+        selected.push(entry);
+    }
+
+    if freed < to_free {
+        tracing::error!("Failed to free enough space in the cache directory");
+    };
+
+    selected
+}
+
+/// Select entries to remove from the remaining entries using the given method
+/// `planned` is the number of bytes which are already selected for removal by other methods,
+/// `protected` entries (within a feed quota) are not selected by least recently used methods
+fn plan_cleanup_method(
+    storage: &AudioStorage,
+    entries: &[CacheEntry],
+    protected: &BTreeSet<String>,
+    planned: u64,
+    method: &CleanupMethod,
+) -> eyre::Result<Vec<CacheEntry>> {
+    let unprotected = || {
+        entries
+            .iter()
+            .filter(|entry| !protected.contains(&entry.path))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+
+    match method {
+        CleanupMethod::MaxStorage(size) => {
+            let actual_sz = entries.iter().map(CacheEntry::stored_size).sum::<u64>();
+
+            if actual_sz <= *size {
+                tracing::info!("Skipping (Max size not reached)");
+                return Ok(Vec::new());
+            };
+
+            tracing::info!("Running Cleanup (Max Storage)");
+
+            // Calculate how much space to free
+            Ok(least_recently_used(&unprotected(), actual_sz - size))
+        }
+        CleanupMethod::MaxAge(age) => {
+            let now = unix_now();
+
+            tracing::info!("Running Cleanup (Max Age)");
+
+            Ok(entries
+                .iter()
+                .filter(|entry| now.saturating_sub(entry.created) > age.as_secs())
+                .cloned()
+                .collect())
+        }
+        CleanupMethod::MinFreeSpace(min_free) => {
+            let Some(available) = storage.available_space()? else {
+                tracing::info!("Skipping (Free space of {} unknown)", storage.describe());
+                return Ok(Vec::new());
+            };
+            let available = available + planned;
+
+            if available >= *min_free {
+                tracing::info!("Skipping (Enough free space)");
+                return Ok(Vec::new());
+            };
+
+            tracing::info!("Running Cleanup (Min Free Space)");
+
+            Ok(least_recently_used(&unprotected(), min_free - available))
+        }
+        CleanupMethod::FeedQuota(quota) => {
+            let mut feed_entries = entries
+                .iter()
+                .filter(|entry| entry.is_in_feed(&quota.url))
+                .collect::<Vec<_>>();
+
+            // Sort files by creation (most recent first)
+            feed_entries.sort_by_key(|entry| std::cmp::Reverse(entry.created));
+
+            // Episodes are kept until the first one exceeds a limit, all older ones are removed
+            let (mut episodes, mut size) = (0, 0);
+            let mut within_quota = true;
+            let mut selected = Vec::new();
+            for entry in feed_entries {
+                episodes += 1;
+                size += entry.stored_size();
+                within_quota = within_quota
+                    && quota.max_episodes.is_none_or(|max| episodes <= max)
+                    && quota.max_bytes().is_none_or(|max| size <= max);
+
+                if !within_quota {
+                    selected.push(entry.clone());
+                }
+            }
+
+            if selected.is_empty() {
+                tracing::info!("Skipping (Quota of {} not reached)", quota.url);
+            } else {
+                tracing::info!("Running Cleanup (Feed Quota of {})", quota.url);
+            }
+
+            Ok(selected)
+        }
+    }
+}
+
+/// Select all entries to remove using all methods of the given policy
+/// Pinned entries are never selected and do not count towards the storage limit
+/// Entries within a feed quota are only selected by `MaxAge` (or a preceding quota of the feed)
+fn plan_cleanup(
+    index: &CacheIndex,
+    storage: &AudioStorage,
+    policy: &CleanupPolicy,
+) -> eyre::Result<Vec<CacheEntry>> {
+    let pins = index.pins();
+    let (pinned, mut remaining): (Vec<_>, Vec<_>) = index
+        .entries()
+        .into_iter()
+        .partition(|entry| pins.covers(entry));
+    let mut selected: Vec<CacheEntry> = Vec::new();
+    let mut protected = BTreeSet::new();
+
+    if !pinned.is_empty() {
+        tracing::info!(
+            "Ignoring {} pinned files ({:.1} MB)",
+            pinned.len(),
+            pinned.iter().map(CacheEntry::stored_size).sum::<u64>() as f64 / 1e6
+        );
+    }
+
+    for method in &policy.methods {
+        let planned = selected.iter().map(CacheEntry::stored_size).sum();
+        let method_selected =
+            plan_cleanup_method(storage, &remaining, &protected, planned, method)?;
+
+        remaining.retain(|entry| !method_selected.iter().any(|s| s.path == entry.path));
+        selected.extend(method_selected);
+
+        // The remaining episodes of the feed are within its quota
+        if let CleanupMethod::FeedQuota(quota) = method {
+            protected.extend(
+                remaining
+                    .iter()
+                    .filter(|entry| entry.is_in_feed(&quota.url))
+                    .map(|entry| entry.path.clone()),
+            );
+        }
+    }
+
+    Ok(selected)
+}
+
+/// Cleanup cache by removing unneeded elements using all methods of the given policy
+/// Directories left empty are removed as well
+/// Demo directory and pinned episodes are always ignored
+/// With `dry_run` nothing is removed, the report contains the elements which would be removed
+/// Runs with the same policy wait for each other
+pub async fn run_cleanup(
+    index: &Arc<CacheIndex>,
+    storage: &Arc<AudioStorage>,
+    policy: &CleanupPolicy,
+    dry_run: bool,
+) -> eyre::Result<CleanupReport> {
+    let _running = policy.running.lock().await;
+
+    // Planning queries the filesystem (free space), which is blocking
+    let planned = {
+        let (index, storage, policy) = (index.clone(), storage.clone(), policy.clone());
+        tokio::task::spawn_blocking(move || plan_cleanup(&index, &storage, &policy))
+            .await
+            .unwrap_or_else(|e| Err(eyre!("Cleanup planning failed: {e}")))
+    };
+
+    let selected = match planned {
+        Ok(selected) => selected,
+        Err(e) => {
+            if !dry_run {
+                metrics::CLEANUP_RUNS.with_label_values(&["error"]).inc();
+            }
+            return Err(e);
+        }
+    };
+
+    if dry_run {
+        return Ok(CleanupReport::preview(&selected));
+    }
+
+    let report = CleanupReport::delete(index, storage, &selected).await;
+    if report.files > 0 {
+        index.compact()?;
+    }
+
+    metrics::CLEANUP_RUNS.with_label_values(&["success"]).inc();
+    metrics::CLEANUP_FREED_BYTES.inc_by(report.bytes);
+
+    Ok(report)
+}
+
+/// Async task that runs cleanup and captures all errors
+pub async fn run_cleanup_task(
+    index: Arc<CacheIndex>,
+    storage: Arc<AudioStorage>,
+    policy: CleanupPolicy,
+) {
+    match run_cleanup(&index, &storage, &policy, false).await {
+        Ok(report) => {
+            tracing::info!(
+                "Cleanup Completed (removed {} files and {} empty directories, freed {:.1} MB)",
+                report.files,
+                report.dirs,
+                report.bytes as f64 / 1e6
+            )
+        }
+        Err(e) => tracing::error!("{}", e),
+    }
+}
+
+/// Periodically runs cache cleanup (also when no new podcasts are generated) and removes
+/// directories left empty, even if no cleanup method is configured
+pub async fn run_maintenance_task(
+    index: Arc<CacheIndex>,
+    storage: Arc<AudioStorage>,
+    policy: CleanupPolicy,
+    interval: Duration,
+) {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        tracing::info!("Running periodic cache maintenance");
+        if !policy.is_empty() {
+            run_cleanup_task(index.clone(), storage.clone(), policy.clone()).await;
+        }
+
+        match storage.prune_empty_dirs().await {
+            Ok(0) => {}
+            Ok(dirs) => tracing::info!("Removed {dirs} empty directories"),
+            Err(e) => tracing::error!("{e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const DAY: u64 = 24 * 60 * 60;
+    const NEWS: &str = "https://example.com/news";
+    const BLOG: &str = "https://example.com/blog";
+    const PINNED: &str = "https://example.com/pinned";
+    const ARCHIVE: &str = "https://example.com/archive";
+
+    /// Episode of a feed created and last served the given number of days ago
+    fn episode(path: &str, feed: &str, created: u64, served: u64) -> CacheEntry {
+        let now = unix_now();
+        CacheEntry {
+            path: String::from(path),
+            size: 1000,
+            created: now - created * DAY,
+            last_access: now - served * DAY,
+            feed: Some(String::from(feed)),
+            voice: Some(String::from("af_sky")),
+            text_hash: None,
+            characters: None,
+            duration: None,
+            transcript: true,
+            transcript_size: 100,
+            chapters: 0,
+            pinned: false,
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("feed2podcast-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Paths of the entries selected by the policy
+    fn plan(index: &CacheIndex, storage: &AudioStorage, policy: &CleanupPolicy) -> Vec<String> {
+        let mut paths: Vec<_> = plan_cleanup(index, storage, policy)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[tokio::test]
+    async fn plans_max_age_before_max_storage() {
+        let dir = temp_dir("cleanup-age");
+        let storage = AudioStorage::local(&dir.to_string_lossy());
+        let index = CacheIndex::with_entries(
+            dir.clone(),
+            vec![
+                episode("old", BLOG, 10, 1),
+                episode("unused", BLOG, 2, 5),
+                episode("used", BLOG, 1, 3),
+                episode("recent", BLOG, 1, 0),
+            ],
+        )
+        .unwrap();
+
+        // Without the old episode, one more (with its transcript) exceeds the storage limit
+        let policy =
+            CleanupPolicy::new(Some(2200), Some(Duration::from_secs(7 * DAY)), None, vec![]);
+        let selected = plan(&index, &storage, &policy);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(selected, ["old", "unused"]);
+    }
+
+    #[tokio::test]
+    async fn keeps_pinned_and_quota_protected_episodes() {
+        let dir = temp_dir("cleanup-pins");
+        let storage = AudioStorage::local(&dir.to_string_lossy());
+        let index = CacheIndex::with_entries(
+            dir.clone(),
+            vec![
+                episode("news/oldest", NEWS, 3, 9),
+                episode("news/older", NEWS, 2, 8),
+                episode("news/newest", NEWS, 1, 7),
+                CacheEntry {
+                    pinned: true,
+                    ..episode("blog/pinned", BLOG, 4, 10)
+                },
+                episode("blog/unused", BLOG, 2, 2),
+                episode("blog/used", BLOG, 1, 1),
+                episode("pinned/configured", PINNED, 5, 10),
+                episode("archive/old", ARCHIVE, 5, 10),
+            ],
+        )
+        .unwrap()
+        .with_pinned_feeds(vec![String::from(PINNED)]);
+        index.pin_feed(ARCHIVE, true).unwrap();
+
+        let quota = FeedQuota {
+            url: String::from(NEWS),
+            max_size: None,
+            max_episodes: Some(2),
+        };
+        let max_storage = CleanupPolicy::new(Some(0), None, None, vec![quota.clone()]);
+        let min_free_space = CleanupPolicy::new(None, None, Some(u64::MAX / 2), vec![quota]);
+        let quota_only = CleanupPolicy::new(
+            None,
+            None,
+            None,
+            vec![FeedQuota {
+                url: String::from(NEWS),
+                max_size: Some(0),
+                max_episodes: None,
+            }],
+        );
+
+        let by_max_storage = plan(&index, &storage, &max_storage);
+        let by_min_free_space = plan(&index, &storage, &min_free_space);
+        let by_quota = plan(&index, &storage, &quota_only);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Least recently used eviction skips the episodes within the quota of their feed
+        assert_eq!(by_max_storage, ["blog/unused", "blog/used", "news/oldest"]);
+        assert_eq!(by_min_free_space, by_max_storage);
+
+        // A quota of zero bytes selects all episodes of the feed
+        assert_eq!(by_quota, ["news/newest", "news/older", "news/oldest"]);
+    }
+
+    #[tokio::test]
+    async fn keeps_voice_demos() {
+        let dir = temp_dir("cleanup-demos");
+        let episode = "example.com/news/article/af_sky.mp3";
+        let demo = dir.join("demos/kokoro/af_sky.mp3");
+        for path in [dir.join(episode), demo.clone()] {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, [0; 1000]).unwrap();
+        }
+
+        let storage = Arc::new(AudioStorage::local(&dir.to_string_lossy()));
+        let index = Arc::new(
+            CacheIndex::rebuild(&dir.to_string_lossy(), &storage)
+                .await
+                .unwrap(),
+        );
+        let policy = CleanupPolicy::new(Some(0), None, None, vec![]);
+        let report = run_cleanup(&index, &storage, &policy, false).await.unwrap();
+        let demo_kept = demo.exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(report.paths, [episode]);
+        assert!(demo_kept);
+        assert!(index.entries().is_empty());
+    }
+}
@@ -0,0 +1,748 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime},
+};
+
+use eyre::{Context, eyre};
+use poem_openapi::Object;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+
+use super::{paths::url_to_path, storage::AudioStorage};
+
+/// Append-only log of index changes (one JSON record per line)
+const INDEX_LOG: &str = ".index.jsonl";
+
+/// Minimum number of appended records before the log is compacted after flushing accesses
+const COMPACT_THRESHOLD: usize = 1000;
+
+/// Current unix time in seconds
+pub fn unix_now() -> u64 {
+    unix_time(SystemTime::now())
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Metadata of a cached podcast episode
+#[derive(Clone, Debug, Serialize, Deserialize, Object)]
+pub struct CacheEntry {
+    /// Key of the file in the audio storage (path relative to the cache directory)
+    pub path: String,
+
+    /// File size in bytes
+    pub size: u64,
+
+    /// Time of creation (unix seconds)
+    pub created: u64,
+
+    /// Time the file was last served (unix seconds)
+    pub last_access: u64,
+
+    /// URL of the source feed
+    pub feed: Option<String>,
+
+    /// Voice used for TTS
+    pub voice: Option<String>,
+
+    /// SHA256 hash of the narrated text
+    pub text_hash: Option<String>,
+
+    /// Number of narrated characters
+    #[serde(default)]
+    pub characters: Option<u64>,
+
+    /// Playback duration in seconds
+    #[serde(default)]
+    pub duration: Option<f64>,
+
+    /// Whether a transcript is stored next to the audio
+    #[serde(default)]
+    pub transcript: bool,
+
+    /// Size of the transcript in bytes (0 if there is none)
+    #[serde(default)]
+    pub transcript_size: u64,
+
+    /// Number of chapters (stored with the transcript)
+    #[serde(default)]
+    pub chapters: usize,
+
+    /// Whether the episode is pinned (never removed by cleanup)
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+impl CacheEntry {
+    /// Bytes taken by the episode in the audio storage (audio and transcript)
+    pub fn stored_size(&self) -> u64 {
+        self.size + self.transcript_size
+    }
+
+    /// Whether the entry belongs to the given feed
+    /// Entries without recorded feed (e.g. after rebuilding the index) are matched by their path
+    pub fn is_in_feed(&self, feed_url: &str) -> bool {
+        match &self.feed {
+            Some(feed) => feed == feed_url,
+            None => url_to_path(feed_url).is_ok_and(|feed_dir| {
+                self.path
+                    .starts_with(&format!("{}/", feed_dir.trim_end_matches('/')))
+            }),
+        }
+    }
+}
+
+/// Pinned feeds at the time the snapshot was taken (see `CacheIndex::pins`)
+pub struct Pins {
+    feeds: BTreeSet<String>,
+}
+
+impl Pins {
+    /// Whether the entry is exempt from cleanup (pinned itself or part of a pinned feed)
+    pub fn covers(&self, entry: &CacheEntry) -> bool {
+        entry.pinned
+            || match &entry.feed {
+                Some(feed) => self.feeds.contains(feed),
+                None => self.feeds.iter().any(|feed| entry.is_in_feed(feed)),
+            }
+    }
+}
+
+/// Details of audio which was generated by this instance
+pub struct Narration {
+    /// SHA256 hash of the narrated text
+    pub text_hash: String,
+
+    /// Number of narrated characters
+    pub characters: u64,
+
+    /// Playback duration in seconds
+    pub duration: Option<f64>,
+
+    /// Size of the stored transcript in bytes (`None` if storing it failed)
+    pub transcript: Option<u64>,
+
+    /// Number of chapters
+    pub chapters: usize,
+}
+
+/// Change of the index as stored in the log
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Record {
+    Put(CacheEntry),
+    Access { path: String, time: u64 },
+    Remove { path: String },
+    Pin { path: String, pinned: bool },
+    PinFeed { feed: String, pinned: bool },
+}
+
+/// Narrated characters, size and duration of all episodes of a voice (with known duration)
+#[derive(Clone, Copy, Default)]
+pub struct VoiceTotals {
+    pub characters: u64,
+    pub bytes: u64,
+    pub seconds: f64,
+}
+
+/// Content of the index
+#[derive(Default)]
+struct Entries {
+    episodes: BTreeMap<String, CacheEntry>,
+    pinned_feeds: BTreeSet<String>,
+
+    /// Totals per voice, kept up to date with the episodes
+    voices: BTreeMap<String, VoiceTotals>,
+}
+
+impl Entries {
+    /// Add an entry (replacing the entry with the same path)
+    fn put(&mut self, entry: CacheEntry) {
+        self.remove(&entry.path);
+
+        if let (Some(voice), Some(characters), Some(duration)) =
+            (&entry.voice, entry.characters, entry.duration)
+            && duration > 0.0
+        {
+            let totals = self.voices.entry(voice.clone()).or_default();
+            totals.characters += characters;
+            totals.bytes += entry.size;
+            totals.seconds += duration;
+        }
+
+        self.episodes.insert(entry.path.clone(), entry);
+    }
+
+    /// Remove the entry with the given path
+    fn remove(&mut self, path: &str) -> Option<CacheEntry> {
+        let entry = self.episodes.remove(path)?;
+
+        if let (Some(voice), Some(characters), Some(duration)) =
+            (&entry.voice, entry.characters, entry.duration)
+            && duration > 0.0
+            && let Some(totals) = self.voices.get_mut(voice)
+        {
+            totals.characters = totals.characters.saturating_sub(characters);
+            totals.bytes = totals.bytes.saturating_sub(entry.size);
+            totals.seconds = (totals.seconds - duration).max(0.0);
+        }
+
+        Some(entry)
+    }
+}
+
+/// Write of the index log, performed in order by `run_log_writer`
+enum LogWrite {
+    /// Append encoded records
+    Append(String),
+
+    /// Replace the log by the given records (compaction)
+    Replace(String),
+
+    /// Notify once all previous writes are done
+    Sync(oneshot::Sender<()>),
+}
+
+struct State {
+    entries: Entries,
+    writes: mpsc::UnboundedSender<LogWrite>,
+
+    /// Files served since accesses were last written to the log
+    accessed: BTreeSet<String>,
+
+    /// Number of records appended since the log was last compacted
+    appended: usize,
+}
+
+impl State {
+    fn new(entries: Entries, writes: mpsc::UnboundedSender<LogWrite>) -> Self {
+        Self {
+            entries,
+            writes,
+            accessed: BTreeSet::new(),
+            appended: 0,
+        }
+    }
+
+    /// Queue a record to be appended to the log (see `run_log_writer`)
+    fn append(&mut self, record: &Record) -> eyre::Result<()> {
+        let mut line =
+            serde_json::to_string(record).wrap_err(eyre!("Unable to encode index record"))?;
+        line.push('\n');
+        self.write(LogWrite::Append(line))?;
+        self.appended += 1;
+
+        Ok(())
+    }
+
+    /// Queue replacing the log by the current entries
+    fn compact(&mut self) -> eyre::Result<()> {
+        let content = encode_log(&self.entries)?;
+        self.write(LogWrite::Replace(content))?;
+        self.appended = 0;
+
+        Ok(())
+    }
+
+    fn write(&self, write: LogWrite) -> eyre::Result<()> {
+        self.writes
+            .send(write)
+            .map_err(|_| eyre!("Unable to write cache index, its writer stopped"))
+    }
+}
+
+/// Index of all cached podcast episodes (demos are not indexed)
+/// The index is stored in the cache directory, also if audio is stored elsewhere
+/// Changes are applied in memory and written to the log by a separate task, so the index can be
+/// used on the async runtime
+pub struct CacheIndex {
+    state: Mutex<State>,
+
+    /// Feeds pinned by configuration (not stored in the index)
+    configured_pins: Vec<String>,
+}
+
+/// Replay the index log (missing log results in an empty index)
+fn read_log(cache_dir: &Path) -> eyre::Result<Entries> {
+    let mut entries = Entries::default();
+
+    let log = match File::open(cache_dir.join(INDEX_LOG)) {
+        Ok(log) => log,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(entries),
+        Err(e) => return Err(eyre!("Unable to open cache index: {e}")),
+    };
+
+    for (nr, line) in BufReader::new(log).lines().enumerate() {
+        let line = line.wrap_err(eyre!("Unable to read cache index"))?;
+
+        // A partially written last line (e.g. after a crash) is skipped
+        let record = match serde_json::from_str::<Record>(&line) {
+            Ok(record) => record,
+            Err(e) => {
+                tracing::warn!(
+                    "Skipping invalid cache index record in line {}: {e}",
+                    nr + 1
+                );
+                continue;
+            }
+        };
+
+        match record {
+            Record::Put(entry) => {
+                entries.put(entry);
+            }
+            Record::Access { path, time } => {
+                if let Some(entry) = entries.episodes.get_mut(&path) {
+                    entry.last_access = entry.last_access.max(time);
+                }
+            }
+            Record::Remove { path } => {
+                entries.remove(&path);
+            }
+            Record::Pin { path, pinned } => {
+                if let Some(entry) = entries.episodes.get_mut(&path) {
+                    entry.pinned = pinned;
+                }
+            }
+            Record::PinFeed { feed, pinned } => {
+                if pinned {
+                    entries.pinned_feeds.insert(feed);
+                } else {
+                    entries.pinned_feeds.remove(&feed);
+                }
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Encode a log containing only the current entries
+fn encode_log(entries: &Entries) -> eyre::Result<String> {
+    let mut content = String::new();
+    let records = entries
+        .episodes
+        .values()
+        .map(|entry| Record::Put(entry.clone()))
+        .chain(entries.pinned_feeds.iter().map(|feed| Record::PinFeed {
+            feed: feed.clone(),
+            pinned: true,
+        }));
+    for record in records {
+        content +=
+            &serde_json::to_string(&record).wrap_err(eyre!("Unable to encode index record"))?;
+        content.push('\n');
+    }
+
+    Ok(content)
+}
+
+/// Replace the log by the given content and open it for appending
+fn write_log(cache_dir: &Path, content: &str) -> eyre::Result<File> {
+    let log_path = cache_dir.join(INDEX_LOG);
+    let tmp_path = cache_dir.join(format!("{INDEX_LOG}.tmp"));
+
+    std::fs::write(&tmp_path, content).wrap_err(eyre!("Unable to write cache index"))?;
+    std::fs::rename(&tmp_path, &log_path).wrap_err(eyre!("Unable to replace cache index"))?;
+
+    OpenOptions::new()
+        .append(true)
+        .open(&log_path)
+        .wrap_err(eyre!("Unable to open cache index"))
+}
+
+/// Performs the queued writes of the index log in order (off the async runtime)
+async fn run_log_writer(
+    dir: PathBuf,
+    mut log: File,
+    mut writes: mpsc::UnboundedReceiver<LogWrite>,
+) {
+    while let Some(write) = writes.recv().await {
+        let write = match write {
+            LogWrite::Sync(done) => {
+                let _ = done.send(());
+                continue;
+            }
+            write => write,
+        };
+
+        let dir = dir.clone();
+        let written = tokio::task::spawn_blocking(move || {
+            let result = match write {
+                LogWrite::Append(lines) => log
+                    .write_all(lines.as_bytes())
+                    .wrap_err(eyre!("Unable to write cache index")),
+                LogWrite::Replace(content) => {
+                    write_log(&dir, &content).map(|replaced| log = replaced)
+                }
+                LogWrite::Sync(_) => Ok(()),
+            };
+            (log, result)
+        })
+        .await;
+
+        match written {
+            Ok((written, result)) => {
+                log = written;
+                if let Err(e) = result {
+                    tracing::error!("{e}");
+                }
+            }
+            Err(e) => {
+                tracing::error!("Writing the cache index failed: {e}");
+                return;
+            }
+        }
+    }
+}
+
+impl CacheIndex {
+    /// Load the index of the given cache directory
+    /// The index is rebuilt from the audio storage if it does not exist yet
+    pub async fn open(cache_dir: &str, storage: &AudioStorage) -> eyre::Result<Self> {
+        let dir = PathBuf::from(cache_dir);
+
+        if !dir.join(INDEX_LOG).exists() {
+            tracing::info!(
+                "No cache index found, building it from {}",
+                storage.describe()
+            );
+            return Self::rebuild(cache_dir, storage).await;
+        }
+
+        let entries = read_log(&dir)?;
+
+        Self::load(dir, entries)
+    }
+
+    /// Rebuild the index from the files in the audio storage
+    /// Metadata of files which are already indexed is kept
+    pub async fn rebuild(cache_dir: &str, storage: &AudioStorage) -> eyre::Result<Self> {
+        let dir = PathBuf::from(cache_dir);
+
+        std::fs::create_dir_all(&dir).wrap_err(eyre!("Unable to create cache directory"))?;
+
+        let known = read_log(&dir)?;
+        let mut entries = Entries {
+            pinned_feeds: known.pinned_feeds,
+            ..Entries::default()
+        };
+
+        for file in storage.list().await? {
+            let created = unix_time(file.modified);
+            let entry = match known.episodes.get(&file.key) {
+                Some(entry) => CacheEntry {
+                    size: file.size,
+                    transcript_size: file.transcript_size,
+                    ..entry.clone()
+                },
+                None => CacheEntry {
+                    path: file.key.clone(),
+                    size: file.size,
+                    created,
+                    last_access: created,
+                    feed: None,
+                    voice: Path::new(&file.key)
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().to_string()),
+                    text_hash: None,
+                    characters: None,
+                    duration: None,
+                    transcript: false,
+                    transcript_size: file.transcript_size,
+                    chapters: 0,
+                    pinned: false,
+                },
+            };
+
+            entries.put(entry);
+        }
+
+        Self::load(dir, entries)
+    }
+
+    /// Compact the log of the given entries and start writing changes to it
+    fn load(dir: PathBuf, entries: Entries) -> eyre::Result<Self> {
+        let log = write_log(&dir, &encode_log(&entries)?)?;
+        let (writes, queued) = mpsc::unbounded_channel();
+        tokio::spawn(run_log_writer(dir, log, queued));
+
+        Ok(Self {
+            state: Mutex::new(State::new(entries, writes)),
+            configured_pins: Vec::new(),
+        })
+    }
+
+    /// Additionally pin the given feeds (e.g. from the configuration)
+    pub fn with_pinned_feeds(mut self, feeds: Vec<String>) -> Self {
+        self.configured_pins = feeds;
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Add a newly stored file to the index
+    /// `narration` is missing if the file was stored by another instance sharing the storage
+    pub fn insert(
+        &self,
+        key: &str,
+        size: u64,
+        feed: &str,
+        voice: &str,
+        narration: Option<&Narration>,
+    ) -> eyre::Result<()> {
+        let now = unix_now();
+        let entry = CacheEntry {
+            path: String::from(key),
+            size,
+            created: now,
+            last_access: now,
+            feed: Some(String::from(feed)),
+            voice: Some(String::from(voice)),
+            text_hash: narration.map(|n| n.text_hash.clone()),
+            characters: narration.map(|n| n.characters),
+            duration: narration.and_then(|n| n.duration),
+            transcript: narration.is_some_and(|n| n.transcript.is_some()),
+            transcript_size: narration.and_then(|n| n.transcript).unwrap_or_default(),
+            chapters: narration
+                .filter(|n| n.transcript.is_some())
+                .map_or(0, |n| n.chapters),
+            pinned: false,
+        };
+
+        let mut state = self.lock();
+        state.append(&Record::Put(entry.clone()))?;
+        state.entries.put(entry);
+
+        Ok(())
+    }
+
+    /// Record that a file was served
+    /// Only the in-memory index is updated, accesses are written to the log by `flush`
+    pub fn touch(&self, key: &str) {
+        let now = unix_now();
+
+        let mut state = self.lock();
+        if let Some(entry) = state.entries.episodes.get_mut(key) {
+            entry.last_access = now;
+            state.accessed.insert(String::from(key));
+        }
+    }
+
+    /// Write the accesses recorded since the last flush to the log
+    /// The log is compacted once it contains many more records than entries
+    pub fn flush(&self) -> eyre::Result<()> {
+        let mut state = self.lock();
+
+        for path in std::mem::take(&mut state.accessed) {
+            let Some(time) = state.entries.episodes.get(&path).map(|e| e.last_access) else {
+                continue;
+            };
+            state.append(&Record::Access { path, time })?;
+        }
+
+        if state.appended > COMPACT_THRESHOLD.max(state.entries.episodes.len()) {
+            state.compact()?;
+        }
+
+        Ok(())
+    }
+
+    /// Wait until all changes are written to the log
+    pub async fn sync(&self) {
+        let (done, synced) = oneshot::channel();
+        if self.lock().write(LogWrite::Sync(done)).is_ok() {
+            let _ = synced.await;
+        }
+    }
+
+    /// Remove a file from the index (the file itself has to be deleted from the audio storage)
+    /// Returns the size of the removed file
+    pub fn remove(&self, key: &str) -> eyre::Result<u64> {
+        let mut state = self.lock();
+        let size = state
+            .entries
+            .episodes
+            .get(key)
+            .map_or(0, CacheEntry::stored_size);
+
+        state.append(&Record::Remove {
+            path: String::from(key),
+        })?;
+        state.entries.remove(key);
+
+        Ok(size)
+    }
+
+    /// Get the entry of an indexed file
+    pub fn get(&self, key: &str) -> Option<CacheEntry> {
+        self.lock().entries.episodes.get(key).cloned()
+    }
+
+    /// Snapshot of all indexed files
+    pub fn entries(&self) -> Vec<CacheEntry> {
+        self.lock().entries.episodes.values().cloned().collect()
+    }
+
+    /// Number of indexed files
+    pub fn len(&self) -> usize {
+        self.lock().entries.episodes.len()
+    }
+
+    /// Total size of all indexed files (audio and transcripts) in bytes
+    pub fn size(&self) -> u64 {
+        self.lock()
+            .entries
+            .episodes
+            .values()
+            .map(CacheEntry::stored_size)
+            .sum()
+    }
+
+    /// Totals of the episodes narrated with the given voice (`None` if there are none)
+    pub fn voice_totals(&self, voice: &str) -> Option<VoiceTotals> {
+        self.lock()
+            .entries
+            .voices
+            .get(voice)
+            .copied()
+            .filter(|totals| totals.seconds > 0.0 && totals.characters > 0)
+    }
+
+    /// Pin or unpin an indexed file
+    /// Returns `false` if the file is not indexed
+    pub fn pin(&self, key: &str, pinned: bool) -> eyre::Result<bool> {
+        let mut state = self.lock();
+        match state.entries.episodes.get_mut(key) {
+            Some(entry) => entry.pinned = pinned,
+            None => return Ok(false),
+        };
+        state.append(&Record::Pin {
+            path: String::from(key),
+            pinned,
+        })?;
+
+        Ok(true)
+    }
+
+    /// Pin or unpin all episodes of a feed (including episodes generated later on)
+    pub fn pin_feed(&self, feed: &str, pinned: bool) -> eyre::Result<()> {
+        let mut state = self.lock();
+        state.append(&Record::PinFeed {
+            feed: String::from(feed),
+            pinned,
+        })?;
+        if pinned {
+            state.entries.pinned_feeds.insert(String::from(feed));
+        } else {
+            state.entries.pinned_feeds.remove(feed);
+        }
+
+        Ok(())
+    }
+
+    /// All pinned feeds (pinned using the index and by configuration)
+    pub fn pinned_feeds(&self) -> Vec<String> {
+        let mut feeds = self.lock().entries.pinned_feeds.clone();
+        feeds.extend(self.configured_pins.iter().cloned());
+        feeds.into_iter().collect()
+    }
+
+    /// Snapshot of the pinned feeds to check many entries at once
+    pub fn pins(&self) -> Pins {
+        let mut feeds = self.lock().entries.pinned_feeds.clone();
+        feeds.extend(self.configured_pins.iter().cloned());
+        Pins { feeds }
+    }
+
+    /// Rewrite the log so it only contains the current entries
+    pub fn compact(&self) -> eyre::Result<()> {
+        let mut state = self.lock();
+        state.compact()?;
+        state.accessed.clear();
+
+        Ok(())
+    }
+}
+
+/// Periodically writes recorded accesses to the index log
+pub async fn run_flush_task(index: Arc<CacheIndex>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = index.flush() {
+            tracing::error!("{e}");
+        }
+    }
+}
+
+#[cfg(test)]
+impl CacheIndex {
+    /// Index of the given entries, logged in `dir`
+    pub(super) fn with_entries(dir: PathBuf, entries: Vec<CacheEntry>) -> eyre::Result<Self> {
+        let mut index = Entries::default();
+        for entry in entries {
+            index.put(entry);
+        }
+
+        Self::load(dir, index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn writes_changes_to_the_log() {
+        let dir = std::env::temp_dir().join(format!("feed2podcast-index-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let index = CacheIndex::load(dir.clone(), Entries::default()).unwrap();
+
+        let feed = "https://example.com/feed";
+        index
+            .insert("a/kept/voice.mp3", 100, feed, "voice", None)
+            .unwrap();
+        index
+            .insert("a/removed/voice.mp3", 200, feed, "voice", None)
+            .unwrap();
+        index.touch("a/kept/voice.mp3");
+        index.pin("a/kept/voice.mp3", true).unwrap();
+        index.remove("a/removed/voice.mp3").unwrap();
+        index.pin_feed(feed, true).unwrap();
+        index.flush().unwrap();
+        index.sync().await;
+
+        let entries = read_log(&dir).unwrap();
+        let log = std::fs::read_to_string(dir.join(INDEX_LOG)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(entries.episodes.len(), 1);
+        assert!(entries.episodes["a/kept/voice.mp3"].pinned);
+        assert!(entries.pinned_feeds.contains(feed));
+        assert!(log.contains(r#""op":"access""#));
+
+        // Compacting replaces the log by the current entries
+        let dir = std::env::temp_dir().join(format!("feed2podcast-compact-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let index = CacheIndex::load(dir.clone(), entries).unwrap();
+        index.compact().unwrap();
+        index.sync().await;
+
+        let log = std::fs::read_to_string(dir.join(INDEX_LOG)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(log.lines().count(), 2);
+    }
+}
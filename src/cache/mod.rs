@@ -0,0 +1,14 @@
+mod routes;
+pub use routes::Router;
+
+mod cleanup;
+pub use cleanup::{CleanupPolicy, CleanupReport, run_cleanup_task, run_maintenance_task};
+
+mod index;
+pub use index::{CacheEntry, CacheIndex, Narration, run_flush_task};
+
+mod paths;
+pub use paths::{get_demo_path, get_feed_dir, get_podcast_key, get_transcript_key};
+
+mod storage;
+pub use storage::{AudioStorage, AudioStream};
@@ -0,0 +1,74 @@
+use std::{
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+};
+
+use eyre::eyre;
+use poem::{Error, Result};
+use reqwest::StatusCode;
+use url::Url;
+
+pub(super) const DEMO_DIR: &str = "demos";
+
+/// Convert URL string to posix path
+pub(super) fn url_to_path(url: &str) -> eyre::Result<String> {
+    match Url::parse(url) {
+        Ok(url) => {
+            Ok(String::from(url.host_str().ok_or(eyre!("Got URL without host!"))?) + url.path())
+        }
+        Err(_) => Ok(String::from(url)),
+    }
+}
+
+/// Directory of a feed relative to the cache directory
+pub fn get_feed_dir(url: &str) -> Result<String> {
+    url_to_path(url).map_err(|e| {
+        Error::from_string(
+            format!("Unable to create cache path from feed URL: {e}"),
+            StatusCode::BAD_REQUEST,
+        )
+    })
+}
+
+/// Generates the storage key (path relative to the cache directory) for a given podcast episode.
+pub fn get_podcast_key(url: &str, uid: &str, voice: &str) -> Result<String> {
+    let url_path = get_feed_dir(url)?;
+    let id_path = url_to_path(uid).map_err(|e| {
+        Error::from_string(
+            format!("Unable to create cache path from article UID: {e}"),
+            StatusCode::BAD_REQUEST,
+        )
+    })?;
+    let file_dir = Path::new(&url_path).join(&id_path);
+
+    Ok(file_dir
+        .join(format!("{voice}.mp3"))
+        .to_string_lossy()
+        .to_string())
+}
+
+/// Storage key of the transcript of a podcast episode (stored next to the audio)
+pub fn get_transcript_key(podcast_key: &str) -> String {
+    Path::new(podcast_key)
+        .with_extension("transcript.json")
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Generates file path for a given voice demo.
+/// Creates missing directories
+pub fn get_demo_path(cache_dir: &str, model: &str, voice: &str) -> Result<PathBuf> {
+    let cache_dir = Path::new(cache_dir).join(DEMO_DIR).join(model);
+    let audio_path = cache_dir.join(format!("{voice}.mp3"));
+
+    if !cache_dir.exists() {
+        create_dir_all(cache_dir).map_err(|_| {
+            Error::from_string(
+                "Unable to create cache directory for demos",
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        })?;
+    };
+
+    Ok(audio_path)
+}
@@ -0,0 +1,377 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use poem::{Error, Result, web::Data};
+use poem_openapi::{
+    Object, OpenApi, SecurityScheme,
+    auth::Bearer,
+    param::{Path, Query},
+    payload::Json,
+};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    audio::process::PostProcessing,
+    cache::{
+        self, AudioStorage, CacheIndex, CleanupPolicy,
+        cleanup::{CleanupReport, run_cleanup},
+        index::{CacheEntry, unix_now},
+    },
+    content::{Artworks, generate_podcast},
+    data::{Feed2PodcastAdmin, Feed2PodcastPresets, Feed2PodcastTTSConfig, Feed2PodcastURLs},
+    queue::GenerationQueue,
+    schemas::CategoryTags,
+};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Aggregated statistics for a group of cached episodes
+#[derive(Object)]
+pub struct GroupStats {
+    /// Name of the group (feed URL or voice)
+    pub name: String,
+    /// Number of cached episodes
+    pub episodes: usize,
+    /// Size of cached episodes in bytes
+    pub size: u64,
+}
+
+/// Aggregated cache statistics
+#[derive(Object)]
+pub struct CacheStats {
+    /// Number of cached episodes
+    pub episodes: usize,
+    /// Size of cached episodes in bytes
+    pub size: u64,
+    /// Number of pinned episodes (never removed by cleanup)
+    pub pinned_episodes: usize,
+    /// Size of pinned episodes in bytes (not counted towards the cache size limit)
+    pub pinned_size: u64,
+    /// Pinned feeds
+    pub pinned_feeds: Vec<String>,
+    /// Statistics per feed (episodes without known feed are grouped as "unknown")
+    pub feeds: Vec<GroupStats>,
+    /// Statistics per voice
+    pub voices: Vec<GroupStats>,
+}
+
+/// Group entries by the given key
+fn group_stats<'a>(
+    entries: impl Iterator<Item = &'a CacheEntry>,
+    key: impl Fn(&CacheEntry) -> Option<String>,
+) -> Vec<GroupStats> {
+    let mut groups: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+    for entry in entries {
+        let group = groups
+            .entry(key(entry).unwrap_or(String::from("unknown")))
+            .or_default();
+        group.0 += 1;
+        group.1 += entry.stored_size();
+    }
+
+    groups
+        .into_iter()
+        .map(|(name, (episodes, size))| GroupStats {
+            name,
+            episodes,
+            size,
+        })
+        .collect()
+}
+
+/// Bearer token required by endpoints which modify the cache (`server.admin_token`)
+#[derive(SecurityScheme)]
+#[oai(ty = "bearer")]
+struct AdminToken(Bearer);
+
+/// Check the given token against the configured admin token
+fn authorize(token: &AdminToken, admin: &Feed2PodcastAdmin) -> Result<()> {
+    let Some(expected) = &admin.token else {
+        return Err(Error::from_string(
+            "Cache administration is disabled (no admin token configured)",
+            StatusCode::FORBIDDEN,
+        ));
+    };
+
+    // Comparing hashes doesn't reveal how much of the token matches
+    if Sha256::digest(token.0.token.as_bytes()) != Sha256::digest(expected.expose().as_bytes()) {
+        return Err(Error::from_string(
+            "Invalid admin token",
+            StatusCode::UNAUTHORIZED,
+        ));
+    }
+
+    Ok(())
+}
+
+/// Convert errors of the cache index into an API error
+fn index_error(e: eyre::Report) -> Error {
+    Error::from_string(
+        format!("Unable to update cache index: {e}"),
+        StatusCode::INTERNAL_SERVER_ERROR,
+    )
+}
+
+pub struct Router;
+
+#[OpenApi(prefix_path = "cache", tag = "CategoryTags::Cache")]
+impl Router {
+    /// List cached podcast episodes
+    #[oai(path = "/episodes", method = "get")]
+    async fn list_episodes(
+        &self,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+
+        /// Only episodes of the given feed URL
+        Query(feed): Query<Option<String>>,
+        /// Only episodes using the given voice
+        Query(voice): Query<Option<String>>,
+        /// Only episodes created more than the given number of days ago
+        Query(older_than): Query<Option<u64>>,
+        /// Only episodes created less than the given number of days ago
+        Query(newer_than): Query<Option<u64>>,
+        /// Only pinned (or unpinned) episodes, including episodes of pinned feeds
+        Query(pinned): Query<Option<bool>>,
+    ) -> Result<Json<Vec<CacheEntry>>> {
+        if let Some(feed) = &feed {
+            cache::get_feed_dir(feed)?;
+        }
+        let now = unix_now();
+        let pins = cache_index.pins();
+
+        Ok(Json(
+            cache_index
+                .entries()
+                .into_iter()
+                .filter(|entry| feed.as_ref().is_none_or(|feed| entry.is_in_feed(feed)))
+                .filter(|entry| voice.is_none() || entry.voice == voice)
+                .filter(|entry| {
+                    older_than
+                        .is_none_or(|days| now.saturating_sub(entry.created) > days * SECS_PER_DAY)
+                })
+                .filter(|entry| {
+                    newer_than
+                        .is_none_or(|days| now.saturating_sub(entry.created) < days * SECS_PER_DAY)
+                })
+                .filter(|entry| pinned.is_none_or(|pinned| pins.covers(entry) == pinned))
+                .collect(),
+        ))
+    }
+
+    /// Aggregated statistics of the cache
+    #[oai(path = "/stats", method = "get")]
+    async fn get_stats(&self, Data(cache_index): Data<&Arc<CacheIndex>>) -> Json<CacheStats> {
+        let entries = cache_index.entries();
+        let pins = cache_index.pins();
+        let pinned = entries
+            .iter()
+            .filter(|entry| pins.covers(entry))
+            .collect::<Vec<_>>();
+
+        Json(CacheStats {
+            episodes: entries.len(),
+            size: entries.iter().map(CacheEntry::stored_size).sum(),
+            pinned_episodes: pinned.len(),
+            pinned_size: pinned.iter().map(|entry| entry.stored_size()).sum(),
+            pinned_feeds: cache_index.pinned_feeds(),
+            feeds: group_stats(entries.iter(), |entry| entry.feed.clone()),
+            voices: group_stats(entries.iter(), |entry| entry.voice.clone()),
+        })
+    }
+
+    /// Delete a single cached episode
+    #[oai(path = "/episodes", method = "delete")]
+    async fn delete_episode(
+        &self,
+        token: AdminToken,
+        Data(admin): Data<&Feed2PodcastAdmin>,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+        Data(storage): Data<&Arc<AudioStorage>>,
+
+        /// Path of the episode (as returned by "List Episodes")
+        Query(path): Query<String>,
+    ) -> Result<Json<CleanupReport>> {
+        authorize(&token, admin)?;
+        let entry = cache_index.get(&path).ok_or(Error::from_string(
+            format!("Episode '{path}' is not cached!"),
+            StatusCode::NOT_FOUND,
+        ))?;
+
+        Ok(Json(
+            CleanupReport::delete(cache_index, storage, &[entry]).await,
+        ))
+    }
+
+    /// Delete all cached episodes of a feed
+    #[oai(path = "/feeds", method = "delete")]
+    async fn delete_feed(
+        &self,
+        token: AdminToken,
+        Data(admin): Data<&Feed2PodcastAdmin>,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+        Data(storage): Data<&Arc<AudioStorage>>,
+
+        /// The Feed URL
+        Query(url): Query<String>,
+    ) -> Result<Json<CleanupReport>> {
+        authorize(&token, admin)?;
+        cache::get_feed_dir(&url)?;
+        let entries = cache_index
+            .entries()
+            .into_iter()
+            .filter(|entry| entry.is_in_feed(&url))
+            .collect::<Vec<_>>();
+
+        Ok(Json(
+            CleanupReport::delete(cache_index, storage, &entries).await,
+        ))
+    }
+
+    /// Pin or unpin a single cached episode
+    /// Pinned episodes are never removed by cleanup
+    #[oai(path = "/pins/episodes", method = "put")]
+    async fn pin_episode(
+        &self,
+        token: AdminToken,
+        Data(admin): Data<&Feed2PodcastAdmin>,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+
+        /// Path of the episode (as returned by "List Episodes")
+        Query(path): Query<String>,
+        /// Whether to pin or unpin the episode
+        Query(pinned): Query<Option<bool>>,
+    ) -> Result<Json<CacheEntry>> {
+        authorize(&token, admin)?;
+        if !cache_index
+            .pin(&path, pinned.unwrap_or(true))
+            .map_err(index_error)?
+        {
+            return Err(Error::from_string(
+                format!("Episode '{path}' is not cached!"),
+                StatusCode::NOT_FOUND,
+            ));
+        }
+
+        cache_index.get(&path).map(Json).ok_or(Error::from_string(
+            format!("Episode '{path}' is not cached!"),
+            StatusCode::NOT_FOUND,
+        ))
+    }
+
+    /// Pin or unpin all episodes of a feed (including episodes generated later on)
+    /// Feeds pinned by configuration can't be unpinned
+    #[oai(path = "/pins/feeds", method = "put")]
+    async fn pin_feed(
+        &self,
+        token: AdminToken,
+        Data(admin): Data<&Feed2PodcastAdmin>,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+
+        /// The Feed URL
+        Query(url): Query<String>,
+        /// Whether to pin or unpin the feed
+        Query(pinned): Query<Option<bool>>,
+    ) -> Result<Json<Vec<String>>> {
+        authorize(&token, admin)?;
+        cache::get_feed_dir(&url)?;
+        cache_index
+            .pin_feed(&url, pinned.unwrap_or(true))
+            .map_err(index_error)?;
+
+        Ok(Json(cache_index.pinned_feeds()))
+    }
+
+    /// Generate the audio of an episode again, the cached audio is replaced once it is stored
+    #[oai(path = "/regenerate/:voice", method = "post")]
+    async fn regenerate_episode(
+        &self,
+        token: AdminToken,
+        Data(admin): Data<&Feed2PodcastAdmin>,
+        Data(app_urls): Data<&Feed2PodcastURLs>,
+        Data(tts_conf): Data<&Feed2PodcastTTSConfig>,
+        Data(processing): Data<&PostProcessing>,
+        Data(artworks): Data<&Arc<Artworks>>,
+        Data(queue): Data<&Arc<GenerationQueue>>,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+        Data(storage): Data<&Arc<AudioStorage>>,
+        Data(presets): Data<&Feed2PodcastPresets>,
+
+        /// The voice to use for the podcast
+        Path(voice): Path<String>,
+        /// The Feed URL
+        Query(url): Query<String>,
+        /// The GUID of the article
+        Query(uid): Query<String>,
+        /// HTML elements/CSS Selectors to ignore when parsing the content
+        Query(ignore): Query<Option<Vec<String>>>,
+        /// Whether to normalize text for TTS
+        Query(normalize): Query<bool>,
+    ) -> Result<Json<CacheEntry>> {
+        authorize(&token, admin)?;
+        let key = cache::get_podcast_key(&url, &uid, &voice)?;
+
+        // The previous audio stays available if generating fails
+        let previous = cache_index.get(&key);
+        let preset = presets.for_feed(&url);
+        generate_podcast(
+            &key,
+            &url,
+            &uid,
+            &voice,
+            &ignore.unwrap_or_default(),
+            preset,
+            presets.jingles_for_feed(&url),
+            normalize,
+            false,
+            true,
+            &app_urls.tts,
+            tts_conf,
+            processing,
+            artworks,
+            queue,
+            cache_index,
+            storage,
+        )
+        .await?;
+
+        // Keep pinned episodes pinned
+        if let Some(entry) = previous.filter(|entry| entry.pinned) {
+            cache_index.pin(&entry.path, true).map_err(index_error)?;
+        }
+
+        cache_index.get(&key).map(Json).ok_or(Error::from_string(
+            "Regenerated episode is missing in cache index",
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ))
+    }
+
+    /// Run cache cleanup using the configured limits
+    #[oai(path = "/cleanup", method = "post")]
+    async fn cleanup(
+        &self,
+        token: AdminToken,
+        Data(admin): Data<&Feed2PodcastAdmin>,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+        Data(storage): Data<&Arc<AudioStorage>>,
+        Data(cache_cleanup): Data<&CleanupPolicy>,
+
+        /// Only report what would be deleted
+        Query(dry_run): Query<Option<bool>>,
+    ) -> Result<Json<CleanupReport>> {
+        authorize(&token, admin)?;
+        run_cleanup(
+            cache_index,
+            storage,
+            cache_cleanup,
+            dry_run.unwrap_or(false),
+        )
+        .await
+        .map(Json)
+        .map_err(|e| {
+            Error::from_string(
+                format!("Cache cleanup failed: {e}"),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        })
+    }
+}
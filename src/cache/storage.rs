@@ -0,0 +1,479 @@
+use std::{
+    collections::HashMap,
+    io::SeekFrom,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use bytes::Bytes;
+use eyre::{Context, eyre};
+use futures::TryStreamExt;
+use glob::glob;
+use object_store::{
+    GetOptions, GetRange, ObjectStore, PutPayload,
+    aws::{AmazonS3, AmazonS3Builder},
+    path::Path as ObjectPath,
+    signer::Signer,
+};
+use poem::Body;
+use reqwest::Method;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use super::paths::{DEMO_DIR, get_transcript_key};
+use crate::config::S3Config;
+
+/// Name of the file/object used to check whether the storage is usable
+const PROBE: &str = ".readyz";
+
+/// Audio file in the storage
+pub struct StoredFile {
+    /// Key of the file (path relative to the storage root)
+    pub key: String,
+
+    /// File size in bytes
+    pub size: u64,
+
+    /// Size of the transcript stored next to the audio in bytes (0 if there is none)
+    pub transcript_size: u64,
+
+    /// Time of the last modification
+    pub modified: SystemTime,
+}
+
+/// Audio which is streamed to the client instead of being loaded into memory
+pub struct AudioStream {
+    pub body: Body,
+
+    /// Size of the audio in bytes
+    pub size: u64,
+}
+
+impl AudioStream {
+    /// Stream a local file
+    pub async fn from_file(path: &Path) -> std::io::Result<Self> {
+        Self::from_file_range(path, None).await
+    }
+
+    /// Stream a part of a local file (end of the range is exclusive)
+    pub async fn from_file_range(path: &Path, range: Option<Range<u64>>) -> std::io::Result<Self> {
+        let mut file = tokio::fs::File::open(path).await?;
+        let range = match range {
+            Some(range) => range,
+            None => 0..file.metadata().await?.len(),
+        };
+
+        file.seek(SeekFrom::Start(range.start)).await?;
+
+        Ok(Self {
+            body: Body::from_async_read(file.take(range.end - range.start)),
+            size: range.end - range.start,
+        })
+    }
+}
+
+impl From<Bytes> for AudioStream {
+    fn from(data: Bytes) -> Self {
+        Self {
+            size: data.len() as u64,
+            body: Body::from_bytes(data),
+        }
+    }
+}
+
+/// Whether the path points to an internal file (e.g. the index log)
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+/// Whether the path points to podcast audio (and not e.g. a transcript)
+fn is_audio(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "mp3")
+}
+
+/// List all audio files in the cache directory (voice demos and internal files are ignored)
+fn list_dir(dir: &Path) -> eyre::Result<Vec<StoredFile>> {
+    let demo_dir = dir.join(DEMO_DIR);
+    let files = glob(&format!("{}/**/*", dir.display()))
+        .map_err(|e| eyre!("Failed to read directory: {}", e))?
+        .filter_map(|p| {
+            p.ok()
+                .filter(|p| !(p.starts_with(&demo_dir) || p.is_dir() || is_hidden(p)))
+                .filter(|p| is_audio(p))
+        });
+
+    let mut stored = Vec::new();
+    for path in files {
+        let metadata = match path.metadata() {
+            Ok(meta) => meta,
+            Err(e) => {
+                tracing::error!("Failed to get metadata for {path:?}: {e}");
+                continue;
+            }
+        };
+        let Some(key) = path
+            .strip_prefix(dir)
+            .ok()
+            .map(|p| p.to_string_lossy().to_string())
+        else {
+            continue;
+        };
+
+        let transcript_size = dir
+            .join(get_transcript_key(&key))
+            .metadata()
+            .map_or(0, |meta| meta.len());
+
+        stored.push(StoredFile {
+            key,
+            size: metadata.len(),
+            transcript_size,
+            modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        });
+    }
+
+    Ok(stored)
+}
+
+/// Remove empty directories below `dir` (depth first)
+/// Returns the number of removed directories and whether `dir` itself is empty afterwards
+fn prune_dir(dir: &Path) -> eyre::Result<(usize, bool)> {
+    let mut removed = 0;
+    let mut empty = true;
+
+    for entry in std::fs::read_dir(dir).wrap_err_with(|| format!("Failed to read {dir:?}"))? {
+        let path = entry?.path();
+
+        if !path.is_dir() {
+            empty = false;
+            continue;
+        }
+
+        let (sub_removed, sub_empty) = prune_dir(&path)?;
+        removed += sub_removed;
+
+        if sub_empty && std::fs::remove_dir(&path).is_ok() {
+            removed += 1;
+        } else {
+            empty = false;
+        }
+    }
+
+    Ok((removed, empty))
+}
+
+/// Storage backend for podcast audio
+/// Files are addressed by keys (paths relative to the cache directory or bucket)
+pub enum AudioStorage {
+    /// Files in the local cache directory
+    Local(PathBuf),
+
+    /// Objects in an S3 compatible bucket
+    S3 {
+        store: AmazonS3,
+        bucket: String,
+
+        /// Validity of presigned URLs (presigned redirects are disabled if not set)
+        presign: Option<Duration>,
+    },
+}
+
+impl AudioStorage {
+    /// Storage using the local cache directory
+    pub fn local(cache_dir: &str) -> Self {
+        Self::Local(PathBuf::from(cache_dir))
+    }
+
+    /// Storage using an S3 compatible bucket
+    /// Credentials which are not configured are read from the environment (AWS_*)
+    pub fn s3(conf: &S3Config) -> eyre::Result<Self> {
+        let mut builder = AmazonS3Builder::from_env()
+            .with_bucket_name(&conf.bucket)
+            .with_region(&conf.region);
+
+        if let Some(endpoint) = &conf.endpoint {
+            builder = builder
+                .with_endpoint(endpoint)
+                .with_allow_http(endpoint.starts_with("http://"));
+        }
+        if let Some(access_key_id) = &conf.access_key_id {
+            builder = builder.with_access_key_id(access_key_id);
+        }
+        if let Some(secret_access_key) = &conf.secret_access_key {
+            builder = builder.with_secret_access_key(secret_access_key.expose());
+        }
+
+        Ok(Self::S3 {
+            store: builder
+                .build()
+                .wrap_err(eyre!("Unable to configure S3 storage"))?,
+            bucket: conf.bucket.clone(),
+            presign: conf
+                .presigned_redirects
+                .then(|| Duration::from_secs((conf.presign_expiry as u64) * 60)),
+        })
+    }
+
+    /// Human readable description of the storage
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Local(dir) => format!("Local directory {dir:?}"),
+            Self::S3 { bucket, .. } => format!("S3 bucket '{bucket}'"),
+        }
+    }
+
+    /// Size of a file (`None` if it does not exist)
+    pub async fn size(&self, key: &str) -> eyre::Result<Option<u64>> {
+        match self {
+            Self::Local(dir) => match tokio::fs::metadata(dir.join(key)).await {
+                Ok(metadata) => Ok(Some(metadata.len())),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(eyre!("Failed to get metadata for audio file {key:?}: {e}")),
+            },
+            Self::S3 { store, .. } => match store.head(&ObjectPath::from(key)).await {
+                Ok(meta) => Ok(Some(meta.size)),
+                Err(object_store::Error::NotFound { .. }) => Ok(None),
+                Err(e) => Err(eyre!(
+                    "Failed to get metadata for audio object {key:?}: {e}"
+                )),
+            },
+        }
+    }
+
+    /// Open a file (or the given part of it, end is exclusive) for streaming
+    /// Returns `None` if the file does not exist
+    pub async fn open(
+        &self,
+        key: &str,
+        range: Option<Range<u64>>,
+    ) -> eyre::Result<Option<AudioStream>> {
+        match self {
+            Self::Local(dir) => match AudioStream::from_file_range(&dir.join(key), range).await {
+                Ok(stream) => Ok(Some(stream)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(eyre!("Failed to read audio file {key:?}: {e}")),
+            },
+            Self::S3 { store, .. } => {
+                let options = GetOptions {
+                    range: range.map(GetRange::Bounded),
+                    ..Default::default()
+                };
+
+                match store.get_opts(&ObjectPath::from(key), options).await {
+                    Ok(result) => Ok(Some(AudioStream {
+                        size: result.range.end - result.range.start,
+                        body: Body::from_bytes_stream(result.into_stream()),
+                    })),
+                    Err(object_store::Error::NotFound { .. }) => Ok(None),
+                    Err(e) => Err(eyre!("Failed to read audio object {key:?}: {e}")),
+                }
+            }
+        }
+    }
+
+    /// Write a file (missing directories are created), an existing file is replaced
+    pub async fn write(&self, key: &str, data: Bytes) -> eyre::Result<()> {
+        match self {
+            Self::Local(dir) => {
+                let path = dir.join(key);
+
+                // Directory could have been removed by cache maintenance in the meantime
+                if let Some(parent) = path.parent() {
+                    tokio::fs::create_dir_all(parent)
+                        .await
+                        .wrap_err(eyre!("Unable to create cache directory"))?;
+                }
+
+                // Existing files are replaced at once, so they are never served partially written
+                let tmp_path = path.with_file_name(format!(
+                    ".{}.tmp",
+                    path.file_name().unwrap_or_default().to_string_lossy()
+                ));
+                tokio::fs::write(&tmp_path, data)
+                    .await
+                    .wrap_err(eyre!("Failed to write audio file {key:?}"))?;
+                tokio::fs::rename(&tmp_path, &path)
+                    .await
+                    .wrap_err(eyre!("Failed to replace audio file {key:?}"))
+            }
+            Self::S3 { store, .. } => store
+                .put(&ObjectPath::from(key), PutPayload::from(data))
+                .await
+                .map(|_| ())
+                .wrap_err(eyre!("Failed to write audio object {key:?}")),
+        }
+    }
+
+    /// Delete a file (missing files are ignored)
+    /// Returns the number of removed (now empty) parent directories
+    pub async fn delete(&self, key: &str) -> eyre::Result<usize> {
+        match self {
+            Self::Local(dir) => {
+                let path = dir.join(key);
+
+                match tokio::fs::remove_file(&path).await {
+                    Ok(_) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(eyre!("Failed to remove file {path:?}: {e}")),
+                };
+
+                // Remove parent directories left empty
+                let mut dirs = 0;
+                let mut parent = path.parent();
+                while let Some(parent_dir) =
+                    parent.filter(|d| *d != dir.as_path() && d.starts_with(dir))
+                {
+                    if tokio::fs::remove_dir(parent_dir).await.is_err() {
+                        break;
+                    }
+                    dirs += 1;
+                    parent = parent_dir.parent();
+                }
+
+                Ok(dirs)
+            }
+            Self::S3 { store, .. } => match store.delete(&ObjectPath::from(key)).await {
+                Ok(_) | Err(object_store::Error::NotFound { .. }) => Ok(0),
+                Err(e) => Err(eyre!("Failed to remove audio object {key:?}: {e}")),
+            },
+        }
+    }
+
+    /// Delete the audio of an episode together with its transcript
+    /// Returns the number of removed (now empty) parent directories
+    pub async fn delete_episode(&self, key: &str) -> eyre::Result<usize> {
+        self.delete(&get_transcript_key(key)).await?;
+        self.delete(key).await
+    }
+
+    /// List all stored audio files (voice demos and internal files are ignored)
+    pub async fn list(&self) -> eyre::Result<Vec<StoredFile>> {
+        match self {
+            Self::Local(dir) => {
+                let dir = dir.clone();
+
+                // Walking the directory tree is blocking
+                tokio::task::spawn_blocking(move || list_dir(&dir))
+                    .await
+                    .wrap_err(eyre!("Failed to list audio files"))?
+            }
+            Self::S3 { store, .. } => {
+                let objects = store
+                    .list(None)
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .wrap_err(eyre!("Failed to list audio objects"))?;
+                let sizes: HashMap<_, _> = objects
+                    .iter()
+                    .map(|meta| (meta.location.to_string(), meta.size))
+                    .collect();
+
+                Ok(objects
+                    .into_iter()
+                    .filter(|meta| {
+                        meta.location
+                            .filename()
+                            .is_some_and(|name| !name.starts_with('.'))
+                            && is_audio(Path::new(meta.location.as_ref()))
+                    })
+                    .map(|meta| {
+                        let key = meta.location.to_string();
+                        StoredFile {
+                            transcript_size: sizes
+                                .get(&get_transcript_key(&key))
+                                .copied()
+                                .unwrap_or_default(),
+                            key,
+                            size: meta.size,
+                            modified: meta.last_modified.into(),
+                        }
+                    })
+                    .collect())
+            }
+        }
+    }
+
+    /// Remove directories left empty (e.g. by a crash or manual deletions)
+    /// The cache directory itself and the voice demos are kept, object stores have no directories
+    /// Returns the number of removed directories
+    pub async fn prune_empty_dirs(&self) -> eyre::Result<usize> {
+        match self {
+            Self::Local(dir) => {
+                let dir = dir.clone();
+
+                // Walking the directory tree is blocking
+                tokio::task::spawn_blocking(move || -> eyre::Result<usize> {
+                    let demo_dir = dir.join(DEMO_DIR);
+                    let mut removed = 0;
+
+                    for entry in std::fs::read_dir(&dir)? {
+                        let path = entry?.path();
+                        if !path.is_dir() || path == demo_dir {
+                            continue;
+                        }
+
+                        let (sub_removed, empty) = prune_dir(&path)?;
+                        removed += sub_removed;
+                        if empty && std::fs::remove_dir(&path).is_ok() {
+                            removed += 1;
+                        }
+                    }
+
+                    Ok(removed)
+                })
+                .await
+                .wrap_err(eyre!("Failed to prune empty directories"))?
+            }
+            Self::S3 { .. } => Ok(0),
+        }
+    }
+
+    /// Presigned URL to download a file directly from the storage
+    /// Returns `None` if presigned redirects are not available
+    pub async fn presigned_url(&self, key: &str) -> eyre::Result<Option<String>> {
+        match self {
+            Self::Local(_) | Self::S3 { presign: None, .. } => Ok(None),
+            Self::S3 {
+                store,
+                presign: Some(expiry),
+                ..
+            } => Ok(Some(
+                store
+                    .signed_url(Method::GET, &ObjectPath::from(key), *expiry)
+                    .await
+                    .wrap_err(eyre!("Unable to presign URL for {key:?}"))?
+                    .to_string(),
+            )),
+        }
+    }
+
+    /// Free space available for storing audio
+    /// Returns `None` if the storage has no (known) space limit
+    pub fn available_space(&self) -> eyre::Result<Option<u64>> {
+        match self {
+            Self::Local(dir) => fs4::available_space(dir)
+                .map(Some)
+                .wrap_err(eyre!("Unable to get free space of the cache filesystem")),
+            Self::S3 { .. } => Ok(None),
+        }
+    }
+
+    /// Check whether the storage is usable
+    /// The cache directory has to be writable (a probe file is written and removed), the bucket
+    /// only has to be reachable with valid credentials (looking up a probe object doesn't create
+    /// requests which are billed as writes)
+    pub async fn probe(&self) -> eyre::Result<()> {
+        match self {
+            Self::Local(_) => {
+                self.write(PROBE, Bytes::new()).await?;
+                self.delete(PROBE).await?;
+            }
+            Self::S3 { .. } => {
+                self.size(PROBE).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1184 @@
+use std::{collections::BTreeMap, fmt, path::PathBuf, str::FromStr};
+
+use clap::{Parser, Subcommand};
+use eyre::{Context, Result, eyre};
+use serde::{Deserialize, Serialize, Serializer};
+use url::Url;
+
+#[derive(Parser, Debug)]
+#[command(name = "feed2podcast")]
+#[command(
+    version,
+    about = "Generate podcast feed from text based rss feeds using TTS"
+)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Path to a TOML configuration file
+    #[arg(
+        long,
+        help = "Path to a TOML configuration file (environment variables and command line arguments take precedence)",
+        env = "FEED2PODCAST_CONFIG"
+    )]
+    pub config: Option<PathBuf>,
+
+    /// URL to the API (make sure to change this when changing the port and using the API docs).
+    #[arg(
+        short,
+        long,
+        help = "URL to the API (make sure to change this when changing the port and using the API docs) [default: http://127.0.0.1:3000]",
+        env = "FEED2PODCAST_URL"
+    )]
+    pub url: Option<String>,
+
+    /// The port on which the server listens for requests.
+    #[arg(
+        short,
+        long,
+        help = "The port on which the server listens for requests. [default: 3000]",
+        env = "FEED2PODCAST_PORT"
+    )]
+    pub port: Option<u16>,
+
+    /// Disable the SwaggerUI API docs (/docs).
+    #[arg(
+        short,
+        long,
+        help = "Disable the SwaggerUI API docs (/docs)",
+        env = "FEED2PODCAST_DISABLE_DOCS",
+        num_args = 0..=1,
+        default_missing_value = "true"
+    )]
+    pub disable_docs: Option<bool>,
+
+    /// Number of queued podcast generations after which the server reports not ready
+    #[arg(
+        long,
+        help = "Number of queued podcast generations after which the server reports not ready (/readyz) [default: 10]",
+        env = "FEED2PODCAST_MAX_QUEUE"
+    )]
+    pub max_queue: Option<usize>,
+
+    /// Token required to modify the cache using the API
+    #[arg(
+        long,
+        help = "Token required to modify the cache using the API (sent as bearer token, cache administration is disabled if not set)",
+        env = "FEED2PODCAST_ADMIN_TOKEN"
+    )]
+    pub admin_token: Option<Secret>,
+
+    /// Cache directory for podcast files
+    #[arg(
+        short,
+        long,
+        help = "Cache directory for podcast files [default: ./cache]",
+        env = "FEED2PODCAST_CACHE_DIR"
+    )]
+    pub cache_dir: Option<String>,
+
+    /// URL to a OpenAI compatible TTS API.
+    #[arg(
+        short,
+        long,
+        help = "URL to a OpenAI compatible TTS API. [default: http://127.0.0.1:5000/v1]",
+        env = "FEED2PODCAST_TTS_API"
+    )]
+    pub tts_url: Option<String>,
+
+    /// API Key for the TTS API (sent as bearer token)
+    #[arg(
+        long,
+        help = "API Key for the TTS API (sent as bearer token)",
+        env = "FEED2PODCAST_TTS_API_KEY"
+    )]
+    pub tts_api_key: Option<Secret>,
+
+    /// Available Voices for TTS (uses audio/voices if not set)
+    #[arg(
+        short,
+        long,
+        help = "Comma separated list of available Voices for TTS (uses audio/voices if not set)",
+        env = "FEED2PODCAST_VOICES",
+        value_delimiter = ','
+    )]
+    pub voices: Option<Vec<String>>,
+
+    /// TTS Model to use
+    #[arg(
+        short,
+        long,
+        help = "TTS Model to use [default: kokoro]",
+        env = "FEED2PODCAST_MODEL"
+    )]
+    pub model: Option<String>,
+
+    /// Stream audio to the client while it is synthesized
+    #[arg(
+        long,
+        help = "Stream podcast audio to the client while it is synthesized (TTS server must support streaming responses)",
+        env = "FEED2PODCAST_TTS_STREAM",
+        num_args = 0..=1,
+        default_missing_value = "true"
+    )]
+    pub tts_stream: Option<bool>,
+
+    /// Target loudness of the synthesized audio
+    #[arg(
+        long,
+        help = "Normalize the loudness of the synthesized audio to the given LUFS (e.g. -16)",
+        env = "FEED2PODCAST_LOUDNESS",
+        allow_negative_numbers = true
+    )]
+    pub loudness: Option<f64>,
+
+    /// Trim silence of the synthesized audio
+    #[arg(
+        long,
+        help = "Trim leading and trailing silence of the synthesized audio",
+        env = "FEED2PODCAST_TRIM_SILENCE",
+        num_args = 0..=1,
+        default_missing_value = "true"
+    )]
+    pub trim_silence: Option<bool>,
+
+    /// Max cache size
+    #[arg(
+        long,
+        help = "Maximum cache size GB (least recently served podcasts are removed first)",
+        env = "FEED2PODCAST_MAX_CACHE_SIZE"
+    )]
+    pub cache_size: Option<u32>,
+
+    /// Max cache age
+    #[arg(
+        long,
+        help = "Maximum cache age in days",
+        env = "FEED2PODCAST_MAX_CACHE_AGE"
+    )]
+    pub cache_age: Option<u32>,
+
+    /// Min free disk space
+    #[arg(
+        long,
+        help = "Minimum free space in GB on the filesystem holding the cache (least recently served podcasts are removed first)",
+        env = "FEED2PODCAST_MIN_FREE_SPACE"
+    )]
+    pub cache_min_free_space: Option<u32>,
+
+    /// Cache maintenance interval
+    #[arg(
+        long,
+        help = "Interval in minutes in which cache maintenance (cleanup and removal of empty directories) runs periodically (0 disables it) [default: 60]",
+        env = "FEED2PODCAST_CACHE_MAINTENANCE_INTERVAL"
+    )]
+    pub cache_maintenance_interval: Option<u32>,
+
+    /// Pinned feeds
+    #[arg(
+        long,
+        help = "Comma separated list of feed URLs whose episodes are never removed by cache cleanup",
+        env = "FEED2PODCAST_PINNED_FEEDS",
+        value_delimiter = ','
+    )]
+    pub pinned_feeds: Option<Vec<String>>,
+
+    /// S3 bucket for podcast audio
+    #[arg(
+        long,
+        help = "Store podcast audio in the given S3 bucket instead of the cache directory (credentials are read from AWS_ACCESS_KEY_ID/AWS_SECRET_ACCESS_KEY)",
+        env = "FEED2PODCAST_S3_BUCKET"
+    )]
+    pub s3_bucket: Option<String>,
+
+    /// S3 endpoint
+    #[arg(
+        long,
+        help = "Endpoint of an S3 compatible object storage (e.g. MinIO)",
+        env = "FEED2PODCAST_S3_ENDPOINT"
+    )]
+    pub s3_endpoint: Option<String>,
+
+    /// S3 region
+    #[arg(
+        long,
+        help = "Region of the S3 bucket [default: us-east-1]",
+        env = "FEED2PODCAST_S3_REGION"
+    )]
+    pub s3_region: Option<String>,
+
+    /// Redirect to presigned S3 URLs
+    #[arg(
+        long,
+        help = "Serve cached podcast audio as redirect to a presigned S3 URL",
+        env = "FEED2PODCAST_S3_PRESIGNED_REDIRECTS",
+        num_args = 0..=1,
+        default_missing_value = "true"
+    )]
+    pub s3_presigned_redirects: Option<bool>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Rebuild the cache index from the files in the cache directory (stop the server first)
+    RebuildIndex,
+}
+
+/// String value which is hidden when printed or serialized (e.g. API keys)
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Secret {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(Self(String::from(s)))
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\"<redacted>\"")
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str("<redacted>")
+    }
+}
+
+/// Server settings (`[server]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Public URL of the API
+    pub url: String,
+
+    /// Port on which the server listens
+    pub port: u16,
+
+    /// Disable the SwaggerUI API docs
+    pub disable_docs: bool,
+
+    /// Number of queued podcast generations after which the server reports not ready
+    pub max_queue: usize,
+
+    /// Token required to modify the cache using the API (disabled if not set)
+    pub admin_token: Option<Secret>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            url: String::from("http://127.0.0.1:3000"),
+            port: 3000,
+            disable_docs: false,
+            max_queue: 10,
+            admin_token: None,
+        }
+    }
+}
+
+/// Cache settings (`[cache]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Cache directory for podcast files
+    pub dir: String,
+
+    /// Maximum cache size in GB
+    pub max_size: Option<u32>,
+
+    /// Maximum cache age in days
+    pub max_age: Option<u32>,
+
+    /// Minimum free space in GB on the filesystem holding the cache
+    pub min_free_space: Option<u32>,
+
+    /// Interval in minutes in which cache maintenance (cleanup and removal of empty directories)
+    /// runs periodically (0 disables it)
+    pub maintenance_interval: u32,
+
+    /// Feed URLs whose episodes are never removed by cleanup
+    pub pinned_feeds: Vec<String>,
+
+    /// Limits for the episodes of single feeds (`[[cache.feed_quotas]]`)
+    pub feed_quotas: Vec<FeedQuota>,
+
+    /// Store podcast audio in an S3 compatible object storage (`[cache.s3]`)
+    pub s3: Option<S3Config>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            dir: String::from("./cache"),
+            max_size: None,
+            max_age: None,
+            min_free_space: None,
+            maintenance_interval: 60,
+            pinned_feeds: Vec::new(),
+            feed_quotas: Vec::new(),
+            s3: None,
+        }
+    }
+}
+
+/// S3 compatible object storage for podcast audio (`[cache.s3]`)
+/// Index and voice demos are still stored in the cache directory
+/// Every instance sharing the bucket runs its own cleanup using its own index, so objects stored
+/// by one instance can be removed by another one (they are generated again when requested)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct S3Config {
+    /// Name of the bucket
+    pub bucket: String,
+
+    /// Endpoint of the object storage (uses AWS if not set)
+    pub endpoint: Option<String>,
+
+    /// Region of the bucket
+    pub region: String,
+
+    /// Access key (uses AWS_ACCESS_KEY_ID if not set)
+    pub access_key_id: Option<String>,
+
+    /// Secret access key (uses AWS_SECRET_ACCESS_KEY if not set)
+    pub secret_access_key: Option<Secret>,
+
+    /// Serve cached audio as redirect to a presigned URL instead of proxying it
+    pub presigned_redirects: bool,
+
+    /// Validity of presigned URLs in minutes
+    pub presign_expiry: u32,
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        Self {
+            bucket: String::new(),
+            endpoint: None,
+            region: String::from("us-east-1"),
+            access_key_id: None,
+            secret_access_key: None,
+            presigned_redirects: false,
+            presign_expiry: 60,
+        }
+    }
+}
+
+/// Cache limits of a single feed (`[[cache.feed_quotas]]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeedQuota {
+    /// The Feed URL
+    pub url: String,
+
+    /// Maximum size of the cached episodes of the feed in MB
+    pub max_size: Option<u32>,
+
+    /// Maximum number of cached episodes of the feed
+    pub max_episodes: Option<u32>,
+}
+
+impl FeedQuota {
+    /// Maximum size of the cached episodes of the feed in bytes
+    pub fn max_bytes(&self) -> Option<u64> {
+        self.max_size.map(|sz| (sz as u64) * (1e6 as u64))
+    }
+}
+
+/// TTS backend settings (`[tts]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TtsConfig {
+    /// URL to a OpenAI compatible TTS API
+    pub url: String,
+
+    /// API Key for the TTS API
+    pub api_key: Option<Secret>,
+
+    /// TTS Model to use
+    pub model: String,
+
+    /// Available voices (uses audio/voices if not set)
+    pub voices: Option<Vec<String>>,
+
+    /// Forward audio to the client while it is synthesized
+    pub stream: bool,
+}
+
+impl Default for TtsConfig {
+    fn default() -> Self {
+        Self {
+            url: String::from("http://127.0.0.1:5000/v1"),
+            api_key: None,
+            model: String::from("kokoro"),
+            voices: None,
+            stream: false,
+        }
+    }
+}
+
+/// Post-processing of the synthesized audio (`[audio]`)
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioConfig {
+    /// Target loudness in LUFS (EBU R128), the loudness is not changed if not set
+    pub loudness: Option<f64>,
+
+    /// Trim leading and trailing silence of every TTS response
+    pub trim_silence: bool,
+}
+
+fn default_normalize() -> bool {
+    true
+}
+
+/// Predefined podcast feed (`[feeds.<name>]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeedPreset {
+    /// The Feed URL
+    pub url: String,
+
+    /// The voice to use for the podcast
+    pub voice: String,
+
+    /// HTML elements/CSS Selectors to ignore when parsing the content
+    #[serde(default)]
+    pub ignore: Vec<String>,
+
+    /// Whether to normalize text for TTS
+    #[serde(default = "default_normalize")]
+    pub normalize: bool,
+
+    /// Maximum size of the cached episodes of the feed in MB
+    pub max_size: Option<u32>,
+
+    /// Maximum number of cached episodes of the feed
+    pub max_episodes: Option<u32>,
+
+    /// Podcast metadata overriding the values derived from the feed
+    #[serde(default)]
+    pub podcast: PodcastMetadata,
+
+    /// Spoken before the article (template, see `TEMPLATE_VARIABLES`)
+    pub intro: Option<String>,
+
+    /// Spoken after the article (template, see `TEMPLATE_VARIABLES`)
+    pub outro: Option<String>,
+
+    /// Audio files joined with the narrated article
+    #[serde(default)]
+    pub jingles: JingleConfig,
+}
+
+/// Time window of the items combined into one digest episode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestPeriod {
+    /// One episode per day
+    #[default]
+    Daily,
+
+    /// One episode per week (starting on Monday)
+    Weekly,
+}
+
+fn default_digest_episodes() -> u32 {
+    7
+}
+
+/// Podcast combining the items of one or more feeds into one episode per day or week
+/// (`[digests.<name>]`)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DigestPreset {
+    /// The Feed URLs
+    pub feeds: Vec<String>,
+
+    /// The voice to use for the podcast
+    pub voice: String,
+
+    /// Time window of an episode
+    #[serde(default)]
+    pub period: DigestPeriod,
+
+    /// Title of the podcast (uses the title of the first feed if not set)
+    pub title: Option<String>,
+
+    /// Number of episodes listed in the feed
+    #[serde(default = "default_digest_episodes")]
+    pub episodes: u32,
+
+    /// HTML elements/CSS Selectors to ignore when parsing the content
+    #[serde(default)]
+    pub ignore: Vec<String>,
+
+    /// Whether to normalize text for TTS
+    #[serde(default = "default_normalize")]
+    pub normalize: bool,
+
+    /// Podcast metadata overriding the values derived from the first feed
+    #[serde(default)]
+    pub podcast: PodcastMetadata,
+
+    /// Spoken before every article (template, see `TEMPLATE_VARIABLES`)
+    pub separator: Option<String>,
+
+    /// Spoken before the first article (template, see `TEMPLATE_VARIABLES`)
+    pub intro: Option<String>,
+
+    /// Spoken after the last article (template, see `TEMPLATE_VARIABLES`)
+    pub outro: Option<String>,
+
+    /// Audio files joined with the narrated articles
+    #[serde(default)]
+    pub jingles: JingleConfig,
+}
+
+/// Variables available in intro and outro templates (e.g. `{title}, by {author}`)
+pub const TEMPLATE_VARIABLES: [&str; 4] = ["title", "author", "date", "feed"];
+
+/// Type of a podcast (`itunes:type`)
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PodcastType {
+    /// Episodes are listed newest first
+    Episodic,
+
+    /// Episodes are listed oldest first
+    Serial,
+}
+
+impl PodcastType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Episodic => "episodic",
+            Self::Serial => "serial",
+        }
+    }
+}
+
+/// Podcast metadata of a feed (`[feeds.<name>.podcast]`), emitted in the `itunes` namespace
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PodcastMetadata {
+    /// URL of the artwork
+    pub image: Option<String>,
+
+    /// Author of the podcast
+    pub author: Option<String>,
+
+    /// Apple Podcasts categories, subcategories are separated by `/` (e.g. `News/Tech News`)
+    pub categories: Vec<String>,
+
+    /// Whether the podcast contains explicit content
+    pub explicit: Option<bool>,
+
+    /// Type of the podcast
+    #[serde(rename = "type")]
+    pub podcast_type: Option<PodcastType>,
+
+    /// Description of the podcast
+    pub summary: Option<String>,
+}
+
+/// MP3 files joined with the narrated article of a feed (`[feeds.<name>.jingles]`)
+/// The files must have the same sample rate and channels as the audio of the TTS server
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JingleConfig {
+    /// Played at the start of every episode
+    pub intro: Option<PathBuf>,
+
+    /// Played at the end of every episode
+    pub outro: Option<PathBuf>,
+
+    /// Played between the chapters of an episode
+    pub stinger: Option<PathBuf>,
+}
+
+/// Effective configuration of the server
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub cache: CacheConfig,
+    pub tts: TtsConfig,
+    pub audio: AudioConfig,
+    pub feeds: BTreeMap<String, FeedPreset>,
+    pub digests: BTreeMap<String, DigestPreset>,
+}
+
+impl Config {
+    /// Quotas of all feeds (from `cache.feed_quotas` and feed presets)
+    pub fn feed_quotas(&self) -> Vec<FeedQuota> {
+        self.cache
+            .feed_quotas
+            .iter()
+            .cloned()
+            .chain(
+                self.feeds
+                    .values()
+                    .filter(|preset| preset.max_size.is_some() || preset.max_episodes.is_some())
+                    .map(|preset| FeedQuota {
+                        url: preset.url.clone(),
+                        max_size: preset.max_size,
+                        max_episodes: preset.max_episodes,
+                    }),
+            )
+            .collect()
+    }
+
+    /// Load configuration with precedence: defaults < config file < environment < command line
+    pub fn load(args: Args) -> Result<Self> {
+        let mut config = match &args.config {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+
+        config.apply_args(args);
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Read configuration from a TOML file
+    fn from_file(path: &PathBuf) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .wrap_err(eyre!("Unable to read config file {}", path.display()))?;
+
+        toml::from_str(&content).map_err(|e| eyre!("Invalid config file {}: {e}", path.display()))
+    }
+
+    /// Override values with the ones given as environment variables or command line arguments
+    fn apply_args(&mut self, args: Args) {
+        if let Some(url) = args.url {
+            self.server.url = url;
+        }
+        if let Some(port) = args.port {
+            self.server.port = port;
+        }
+        if let Some(disable_docs) = args.disable_docs {
+            self.server.disable_docs = disable_docs;
+        }
+        if let Some(max_queue) = args.max_queue {
+            self.server.max_queue = max_queue;
+        }
+        if args.admin_token.is_some() {
+            self.server.admin_token = args.admin_token;
+        }
+        if let Some(dir) = args.cache_dir {
+            self.cache.dir = dir;
+        }
+        if args.cache_size.is_some() {
+            self.cache.max_size = args.cache_size;
+        }
+        if args.cache_age.is_some() {
+            self.cache.max_age = args.cache_age;
+        }
+        if args.cache_min_free_space.is_some() {
+            self.cache.min_free_space = args.cache_min_free_space;
+        }
+        if let Some(interval) = args.cache_maintenance_interval {
+            self.cache.maintenance_interval = interval;
+        }
+        if let Some(feeds) = args.pinned_feeds {
+            self.cache.pinned_feeds = feeds;
+        }
+        if args.s3_bucket.is_some()
+            || args.s3_endpoint.is_some()
+            || args.s3_region.is_some()
+            || args.s3_presigned_redirects.is_some()
+        {
+            let s3 = self.cache.s3.get_or_insert_default();
+            if let Some(bucket) = args.s3_bucket {
+                s3.bucket = bucket;
+            }
+            if args.s3_endpoint.is_some() {
+                s3.endpoint = args.s3_endpoint;
+            }
+            if let Some(region) = args.s3_region {
+                s3.region = region;
+            }
+            if let Some(presigned_redirects) = args.s3_presigned_redirects {
+                s3.presigned_redirects = presigned_redirects;
+            }
+        }
+        if let Some(url) = args.tts_url {
+            self.tts.url = url;
+        }
+        if args.tts_api_key.is_some() {
+            self.tts.api_key = args.tts_api_key;
+        }
+        if let Some(model) = args.model {
+            self.tts.model = model;
+        }
+        if let Some(voices) = args.voices {
+            self.tts.voices = Some(voices);
+        }
+        if let Some(stream) = args.tts_stream {
+            self.tts.stream = stream;
+        }
+        if let Some(loudness) = args.loudness {
+            self.audio.loudness = Some(loudness);
+        }
+        if let Some(trim_silence) = args.trim_silence {
+            self.audio.trim_silence = trim_silence;
+        }
+    }
+
+    /// Check configuration values for errors
+    fn validate(&self) -> Result<()> {
+        check_url("server.url", &self.server.url)?;
+        check_url("tts.url", &self.tts.url)?;
+
+        if self.server.max_queue == 0 {
+            return Err(invalid("server.max_queue", "must be greater than 0"));
+        }
+        if self.cache.dir.is_empty() {
+            return Err(invalid("cache.dir", "must not be empty"));
+        }
+        if self.cache.max_size == Some(0) {
+            return Err(invalid("cache.max_size", "must be greater than 0"));
+        }
+        if self.cache.max_age == Some(0) {
+            return Err(invalid("cache.max_age", "must be greater than 0"));
+        }
+        if self.cache.min_free_space == Some(0) {
+            return Err(invalid("cache.min_free_space", "must be greater than 0"));
+        }
+        for feed in &self.cache.pinned_feeds {
+            check_url("cache.pinned_feeds", feed)?;
+        }
+        if let Some(s3) = &self.cache.s3 {
+            if s3.bucket.is_empty() {
+                return Err(invalid("cache.s3.bucket", "must not be empty"));
+            }
+            if let Some(endpoint) = &s3.endpoint {
+                check_url("cache.s3.endpoint", endpoint)?;
+            }
+            if s3.presign_expiry == 0 {
+                return Err(invalid("cache.s3.presign_expiry", "must be greater than 0"));
+            }
+        }
+        for (nr, quota) in self.cache.feed_quotas.iter().enumerate() {
+            let key = format!("cache.feed_quotas[{nr}]");
+            check_url(&format!("{key}.url"), &quota.url)?;
+            if quota.max_size.is_none() && quota.max_episodes.is_none() {
+                return Err(invalid(
+                    &key,
+                    &format!("quota of '{}' needs max_size or max_episodes", quota.url),
+                ));
+            }
+            if quota.max_size == Some(0) {
+                return Err(invalid(
+                    &format!("{key}.max_size"),
+                    &format!("must be greater than 0 (quota of '{}')", quota.url),
+                ));
+            }
+            if quota.max_episodes == Some(0) {
+                return Err(invalid(
+                    &format!("{key}.max_episodes"),
+                    &format!("must be greater than 0 (quota of '{}')", quota.url),
+                ));
+            }
+        }
+        if self.tts.model.is_empty() {
+            return Err(invalid("tts.model", "must not be empty"));
+        }
+        if let Some(voices) = &self.tts.voices
+            && (voices.is_empty() || voices.iter().any(|v| v.trim().is_empty()))
+        {
+            return Err(invalid("tts.voices", "must not contain empty voices"));
+        }
+
+        if let Some(loudness) = self.audio.loudness
+            && !(-70.0..=0.0).contains(&loudness)
+        {
+            return Err(invalid("audio.loudness", "must be between -70 and 0 LUFS"));
+        }
+
+        for (name, preset) in &self.feeds {
+            let key = format!("feeds.{name}");
+            check_name(&key, name)?;
+            check_url(&format!("{key}.url"), &preset.url)?;
+
+            if preset.max_size == Some(0) {
+                return Err(invalid(
+                    &format!("{key}.max_size"),
+                    "must be greater than 0",
+                ));
+            }
+            if preset.max_episodes == Some(0) {
+                return Err(invalid(
+                    &format!("{key}.max_episodes"),
+                    "must be greater than 0",
+                ));
+            }
+            check_voice(
+                &format!("{key}.voice"),
+                &preset.voice,
+                self.tts.voices.as_deref(),
+            )?;
+            check_selectors(&format!("{key}.ignore"), &preset.ignore)?;
+
+            for (field, template) in [("intro", &preset.intro), ("outro", &preset.outro)] {
+                if let Some(template) = template {
+                    check_template(&format!("{key}.{field}"), template)?;
+                }
+            }
+
+            check_podcast(&format!("{key}.podcast"), &preset.podcast)?;
+        }
+
+        for (name, digest) in &self.digests {
+            let key = format!("digests.{name}");
+            check_name(&key, name)?;
+
+            if digest.feeds.is_empty() {
+                return Err(invalid(&format!("{key}.feeds"), "must not be empty"));
+            }
+            for feed in &digest.feeds {
+                check_url(&format!("{key}.feeds"), feed)?;
+            }
+            if digest.episodes == 0 {
+                return Err(invalid(
+                    &format!("{key}.episodes"),
+                    "must be greater than 0",
+                ));
+            }
+            check_voice(
+                &format!("{key}.voice"),
+                &digest.voice,
+                self.tts.voices.as_deref(),
+            )?;
+            check_selectors(&format!("{key}.ignore"), &digest.ignore)?;
+
+            for (field, template) in [
+                ("separator", &digest.separator),
+                ("intro", &digest.intro),
+                ("outro", &digest.outro),
+            ] {
+                if let Some(template) = template {
+                    check_template(&format!("{key}.{field}"), template)?;
+                }
+            }
+
+            check_podcast(&format!("{key}.podcast"), &digest.podcast)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Create error for an invalid config value
+fn invalid(key: &str, msg: &str) -> eyre::Report {
+    eyre!("Invalid config value for `{key}`: {msg}")
+}
+
+/// Check the name of a preset (used in URLs and cache paths)
+fn check_name(key: &str, name: &str) -> Result<()> {
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(invalid(
+            key,
+            "name may only contain ASCII letters, digits, '-' and '_'",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check the voice of a preset (must be one of `voices` if they are configured)
+fn check_voice(key: &str, voice: &str, voices: Option<&[String]>) -> Result<()> {
+    if voice.is_empty() {
+        return Err(invalid(key, "must not be empty"));
+    }
+    if let Some(voices) = voices
+        && !voices.iter().any(|v| v == voice)
+    {
+        return Err(invalid(key, &format!("'{voice}' is not one of tts.voices")));
+    }
+
+    Ok(())
+}
+
+fn check_selectors(key: &str, selectors: &[String]) -> Result<()> {
+    for selector in selectors {
+        scraper::Selector::parse(selector)
+            .map_err(|e| invalid(key, &format!("invalid selector '{selector}': {e}")))?;
+    }
+
+    Ok(())
+}
+
+fn check_podcast(key: &str, podcast: &PodcastMetadata) -> Result<()> {
+    if let Some(image) = &podcast.image {
+        check_url(&format!("{key}.image"), image)?;
+    }
+    if podcast
+        .categories
+        .iter()
+        .any(|category| category.split('/').any(|c| c.trim().is_empty()))
+    {
+        return Err(invalid(
+            &format!("{key}.categories"),
+            "must not contain empty categories",
+        ));
+    }
+
+    Ok(())
+}
+
+fn check_template(key: &str, value: &str) -> Result<()> {
+    let mut rest = value;
+    while let Some(start) = rest.find('{') {
+        let len = rest[start..]
+            .find('}')
+            .ok_or_else(|| invalid(key, "unclosed '{'"))?;
+        let name = &rest[start + 1..start + len];
+        if !TEMPLATE_VARIABLES.contains(&name) {
+            return Err(invalid(
+                key,
+                &format!(
+                    "unknown variable '{{{name}}}' (available: {})",
+                    TEMPLATE_VARIABLES.join(", ")
+                ),
+            ));
+        }
+        rest = &rest[start + len + 1..];
+    }
+
+    Ok(())
+}
+
+fn check_url(key: &str, value: &str) -> Result<()> {
+    Url::parse(value)
+        .map(|_| ())
+        .map_err(|e| invalid(key, &format!("'{value}' is not a valid URL ({e})")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Change of a valid configuration and the key named by the resulting error
+    type Case = (fn(&mut Config), &'static str);
+
+    /// Valid configuration with a feed quota, feed preset and digest
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+            [tts]
+            voices = ["af_sky"]
+
+            [[cache.feed_quotas]]
+            url = "https://example.com/quota"
+            max_episodes = 5
+
+            [feeds.news]
+            url = "https://example.com/news"
+            voice = "af_sky"
+
+            [digests.weekly]
+            feeds = ["https://example.com/news"]
+            voice = "af_sky"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn applies_precedence() {
+        let path = std::env::temp_dir().join(format!("feed2podcast-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[server]\nport = 4000\n\n[cache]\ndir = \"file\"\n\n[tts]\nmodel = \"file\"\n",
+        )
+        .unwrap();
+
+        // SAFETY: no other test reads the environment
+        unsafe {
+            std::env::set_var("FEED2PODCAST_MODEL", "env");
+            std::env::set_var("FEED2PODCAST_CACHE_DIR", "env");
+        }
+        let args = Args::try_parse_from([
+            "feed2podcast",
+            "--config",
+            path.to_str().unwrap(),
+            "--cache-dir",
+            "cli",
+        ]);
+        unsafe {
+            std::env::remove_var("FEED2PODCAST_MODEL");
+            std::env::remove_var("FEED2PODCAST_CACHE_DIR");
+        }
+        let config = Config::load(args.unwrap());
+        std::fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        // Defaults < config file < environment < command line
+        assert_eq!(config.server.url, ServerConfig::default().url);
+        assert_eq!(config.server.port, 4000);
+        assert_eq!(config.tts.model, "env");
+        assert_eq!(config.cache.dir, "cli");
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        config().validate().unwrap();
+
+        let cases: [Case; 35] = [
+            (|c| c.server.url = String::from("no url"), "`server.url`"),
+            (|c| c.tts.url = String::new(), "`tts.url`"),
+            (|c| c.server.max_queue = 0, "`server.max_queue`"),
+            (|c| c.cache.dir = String::new(), "`cache.dir`"),
+            (|c| c.cache.max_size = Some(0), "`cache.max_size`"),
+            (|c| c.cache.max_age = Some(0), "`cache.max_age`"),
+            (
+                |c| c.cache.min_free_space = Some(0),
+                "`cache.min_free_space`",
+            ),
+            (
+                |c| c.cache.pinned_feeds = vec![String::from("feed")],
+                "`cache.pinned_feeds`",
+            ),
+            (
+                |c| c.cache.s3 = Some(S3Config::default()),
+                "`cache.s3.bucket`",
+            ),
+            (
+                |c| {
+                    c.cache.s3 = Some(S3Config {
+                        bucket: String::from("bucket"),
+                        endpoint: Some(String::from("endpoint")),
+                        ..Default::default()
+                    })
+                },
+                "`cache.s3.endpoint`",
+            ),
+            (
+                |c| {
+                    c.cache.s3 = Some(S3Config {
+                        bucket: String::from("bucket"),
+                        presign_expiry: 0,
+                        ..Default::default()
+                    })
+                },
+                "`cache.s3.presign_expiry`",
+            ),
+            (
+                |c| c.cache.feed_quotas[0].url = String::from("quota"),
+                "`cache.feed_quotas[0].url`",
+            ),
+            (
+                |c| c.cache.feed_quotas[0].max_episodes = None,
+                "`cache.feed_quotas[0]`: quota of 'https://example.com/quota' needs",
+            ),
+            (
+                |c| c.cache.feed_quotas[0].max_size = Some(0),
+                "`cache.feed_quotas[0].max_size`: must be greater than 0 (quota of 'https://example.com/quota')",
+            ),
+            (
+                |c| c.cache.feed_quotas[0].max_episodes = Some(0),
+                "`cache.feed_quotas[0].max_episodes`: must be greater than 0 (quota of 'https://example.com/quota')",
+            ),
+            (|c| c.tts.model = String::new(), "`tts.model`"),
+            (
+                |c| c.tts.voices = Some(vec![String::from(" ")]),
+                "`tts.voices`",
+            ),
+            (|c| c.audio.loudness = Some(-80.0), "`audio.loudness`"),
+            (
+                |c| {
+                    let preset = c.feeds.remove("news").unwrap();
+                    c.feeds.insert(String::from("news/"), preset);
+                },
+                "`feeds.news/`",
+            ),
+            (|c| feed(c).url = String::from("news"), "`feeds.news.url`"),
+            (|c| feed(c).max_size = Some(0), "`feeds.news.max_size`"),
+            (
+                |c| feed(c).max_episodes = Some(0),
+                "`feeds.news.max_episodes`",
+            ),
+            (
+                |c| feed(c).voice = String::from("other"),
+                "`feeds.news.voice`",
+            ),
+            (
+                |c| feed(c).ignore = vec![String::from("<")],
+                "`feeds.news.ignore`",
+            ),
+            (
+                |c| feed(c).intro = Some(String::from("{x}")),
+                "`feeds.news.intro`",
+            ),
+            (
+                |c| feed(c).outro = Some(String::from("{")),
+                "`feeds.news.outro`",
+            ),
+            (
+                |c| feed(c).podcast.image = Some(String::from("image")),
+                "`feeds.news.podcast.image`",
+            ),
+            (
+                |c| feed(c).podcast.categories = vec![String::from("News/")],
+                "`feeds.news.podcast.categories`",
+            ),
+            (
+                |c| {
+                    let digest = c.digests.remove("weekly").unwrap();
+                    c.digests.insert(String::from("week ly"), digest);
+                },
+                "`digests.week ly`",
+            ),
+            (|c| digest(c).feeds.clear(), "`digests.weekly.feeds`"),
+            (
+                |c| digest(c).feeds = vec![String::from("news")],
+                "`digests.weekly.feeds`",
+            ),
+            (|c| digest(c).episodes = 0, "`digests.weekly.episodes`"),
+            (
+                |c| digest(c).voice = String::new(),
+                "`digests.weekly.voice`",
+            ),
+            (
+                |c| digest(c).separator = Some(String::from("{x}")),
+                "`digests.weekly.separator`",
+            ),
+            (
+                |c| digest(c).podcast.image = Some(String::from("image")),
+                "`digests.weekly.podcast.image`",
+            ),
+        ];
+
+        for (change, key) in cases {
+            let mut config = config();
+            change(&mut config);
+            let error = config.validate().unwrap_err().to_string();
+            assert!(error.contains(key), "{error} doesn't name {key}");
+        }
+    }
+
+    fn feed(config: &mut Config) -> &mut FeedPreset {
+        config.feeds.get_mut("news").unwrap()
+    }
+
+    fn digest(config: &mut Config) -> &mut DigestPreset {
+        config.digests.get_mut("weekly").unwrap()
+    }
+}
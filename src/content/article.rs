@@ -0,0 +1,73 @@
+use poem::{Error, Result};
+use reqwest::StatusCode;
+
+/// Selector of headings which start a chapter
+const HEADINGS: &str = "h1, h2, h3";
+
+/// Heading of a section of an article
+pub struct Heading {
+    /// Position of the heading in the narrated text (byte offset)
+    pub offset: usize,
+
+    pub title: String,
+}
+
+/// Narrated content of an article
+pub struct Article {
+    pub text: String,
+
+    /// Headings in order of appearance
+    pub headings: Vec<Heading>,
+}
+
+impl Article {
+    /// Extract the narrated text of an article (content of ignored elements is removed)
+    pub fn parse(article: &str, ignore: &[String]) -> Result<Self> {
+        let doc = scraper::Html::parse_document(article);
+
+        // Extract and append main article body
+        let mut text_content: String = doc.root_element().text().collect();
+
+        // Remove ignored elements content
+        let default_ignore = [String::from("style"), String::from("script")];
+        for ignore_tag in ignore.iter().chain(&default_ignore) {
+            let ignore_selector = scraper::Selector::parse(ignore_tag.as_str()).map_err(|e| {
+                Error::from_string(
+                    format!("Invalid selector '{}': {}", ignore_tag, e),
+                    StatusCode::BAD_REQUEST,
+                )
+            })?;
+
+            for element_to_ignore in doc.select(&ignore_selector) {
+                let text_to_ignore = element_to_ignore.text().collect::<String>();
+
+                text_content = text_content.replace(text_to_ignore.trim(), "");
+            }
+        }
+
+        // Headings are searched in order, headings which were ignored are not found
+        let heading_selector = scraper::Selector::parse(HEADINGS).expect("valid selector");
+        let mut headings = Vec::new();
+        let mut cursor = 0;
+        for element in doc.select(&heading_selector) {
+            let title = element.text().collect::<String>();
+            let title = title.trim();
+            if title.is_empty() {
+                continue;
+            }
+
+            if let Some(pos) = text_content[cursor..].find(title) {
+                headings.push(Heading {
+                    offset: cursor + pos,
+                    title: title.split_whitespace().collect::<Vec<_>>().join(" "),
+                });
+                cursor += pos + title.len();
+            }
+        }
+
+        Ok(Self {
+            text: text_content,
+            headings,
+        })
+    }
+}
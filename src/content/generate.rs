@@ -1,142 +1,750 @@
-use std::path::Path;
+use std::{future::Future, iter::Peekable, sync::Arc, vec};
 
-use poem::{Error, Result};
+use bytes::{Bytes, BytesMut};
+use futures::channel::mpsc;
+use poem::{Body, Error, Result};
 use reqwest::StatusCode;
-use rss::Channel;
-use serde_json::json;
-use tokio::sync::Semaphore;
 
+use sha2::{Digest, Sha256};
+use tokio::{sync::OwnedSemaphorePermit, task::JoinHandle};
+
+use super::{
+    Article, Script, render_template,
+    tags::{self, Artworks},
+};
+use crate::{
+    audio::{
+        id3::{ChapterFrame, Tag},
+        jingle::{Jingle, Jingles},
+        mp3::{self, FrameJoiner},
+        process::{PostProcessing, Processed, Segment},
+    },
+    briefing::{self, Briefing},
+    cache::{AudioStorage, CacheIndex, CleanupReport, Narration, get_transcript_key},
+    config::{DigestPreset, FeedPreset, PodcastMetadata},
+    data::Feed2PodcastTTSConfig,
+    digest::{self, Period},
+    feed::fetch_channel,
+    metrics,
+    queue::GenerationQueue,
+    transcript::Transcript,
+    tts,
+};
+
+/// Availability of the audio of an episode
+pub enum Episode {
+    /// The audio was stored already
+    Cached,
+
+    /// The audio was generated and stored
+    Generated,
+
+    /// The audio is being generated
+    /// `body` forwards the audio while it is synthesized, it is stored once it is complete
+    /// (`stored` resolves to whether storing succeeded)
+    /// Its length is unknown, so the response has no `Content-Length`, byte ranges and validators
+    /// (there is no need to record the access, the index entry is created once it is stored)
+    Streaming {
+        body: Body,
+        stored: JoinHandle<bool>,
+    },
+}
+
+/// Part of a recording, the stored audio and transcript are built once it is complete
+enum Part {
+    /// Start of a chapter
+    Chapter(String),
+
+    /// Narrated chunk with its text or jingle
+    Audio(Segment, Option<String>),
+}
+
+/// Audio of the narrated chunks joined to a single file and the resulting transcript
+struct Recording {
+    /// Metadata of the episode
+    tag: Tag,
+    jingles: Arc<Jingles>,
+    joiner: FrameJoiner,
+    parts: Vec<Part>,
+
+    /// Audio of the current chunk
+    speech: BytesMut,
+
+    /// Whether audio was recorded already
+    started: bool,
+
+    /// Chapters which were not started yet (index of the first chunk, title)
+    chapters: Peekable<vec::IntoIter<(usize, String)>>,
+    chunk: usize,
+}
+
+impl Recording {
+    fn new(tag: Tag, chapters: Vec<(usize, String)>, jingles: Arc<Jingles>) -> Self {
+        Self {
+            tag,
+            jingles,
+            joiner: FrameJoiner::default(),
+            parts: Vec::new(),
+            speech: BytesMut::new(),
+            started: false,
+            chapters: chapters.into_iter().peekable(),
+            chunk: 0,
+        }
+    }
+
+    /// Add a jingle if it has the same format as the narration, returns the added audio
+    fn play(&mut self, name: &str, jingle: Option<&Jingle>) -> Bytes {
+        let Some(jingle) = jingle else {
+            return Bytes::new();
+        };
+        if let Some(format) = self.joiner.format()
+            && format != jingle.format
+        {
+            tracing::error!(
+                "Skipping {name} jingle, its format ({}) differs from the narration ({format})",
+                jingle.format
+            );
+            return Bytes::new();
+        }
+
+        let segment = Segment {
+            frames: jingle.frames.clone(),
+            gapless: jingle.gapless,
+            speech: false,
+        };
+        self.parts.push(Part::Audio(segment, None));
+
+        jingle.frames.clone()
+    }
+
+    /// Start the next chunk, returns the audio preceding it (stinger between chapters)
+    fn start_chunk(&mut self) -> Bytes {
+        let mut audio = Bytes::new();
+        if let Some((_, title)) = self
+            .chapters
+            .next_if(|(first_chunk, _)| *first_chunk == self.chunk)
+        {
+            if self.chunk > 0 {
+                let jingles = self.jingles.clone();
+                audio = self.play("stinger", jingles.stinger.as_ref());
+            }
+            self.parts.push(Part::Chapter(title));
+        }
+        self.chunk += 1;
+
+        audio
+    }
+
+    /// Add audio of the current chunk, returns the audio which is complete
+    /// The intro jingle precedes the first audio (its format is known then)
+    fn push(&mut self, data: &[u8]) -> Bytes {
+        let frames = self.joiner.push(data);
+        if frames.is_empty() {
+            return frames;
+        }
+
+        let mut audio = BytesMut::new();
+        if !self.started {
+            self.started = true;
+            let jingles = self.jingles.clone();
+            audio.extend_from_slice(&self.play("intro", jingles.intro.as_ref()));
+        }
+        self.speech.extend_from_slice(&frames);
+        audio.extend_from_slice(&frames);
+
+        audio.freeze()
+    }
+
+    /// Complete the current chunk (its cue ends with the audio recorded so far)
+    fn end_chunk(&mut self, text: &str) {
+        let segment = Segment {
+            frames: self.speech.split().freeze(),
+            gapless: self.joiner.gapless(),
+            speech: true,
+        };
+        self.joiner.end_file();
+        self.parts
+            .push(Part::Audio(segment, Some(String::from(text))));
+    }
+
+    /// Complete the recording, returns the audio following the last chunk (outro jingle)
+    fn finish(&mut self) -> Bytes {
+        let jingles = self.jingles.clone();
+        self.play("outro", jingles.outro.as_ref())
+    }
+
+    /// Stored audio with its transcript and playback duration in seconds, metadata and chapters
+    /// are embedded as ID3 tag
+    /// The audio is processed unless it is disabled or fails (then the frames are joined as they
+    /// are), which blocks until it is encoded
+    fn master(&self, processing: &PostProcessing) -> (Bytes, Transcript, f64) {
+        let segments: Vec<&Segment> = self
+            .parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::Audio(segment, _) => Some(segment),
+                Part::Chapter(_) => None,
+            })
+            .collect();
+
+        let processed = if processing.is_enabled() {
+            processing
+                .apply(&segments)
+                .inspect_err(|e| tracing::warn!("Unable to process synthesized audio: {e}"))
+                .ok()
+        } else {
+            None
+        };
+        let (audio, ends) = match processed {
+            Some(Processed { audio, ends }) => (audio, ends),
+            None => {
+                let mut audio = BytesMut::new();
+                let mut ends = Vec::with_capacity(segments.len());
+                let mut end = 0.0;
+                for segment in &segments {
+                    audio.extend_from_slice(&segment.frames);
+                    end += mp3::duration(&segment.frames);
+                    ends.push(end);
+                }
+                (audio.freeze(), ends)
+            }
+        };
+
+        let mut transcript = Transcript::default();
+        let (mut ends, mut start) = (ends.into_iter(), 0.0);
+        for part in &self.parts {
+            match part {
+                Part::Chapter(title) => transcript.start_chapter(title),
+                Part::Audio(_, text) => {
+                    let end = ends.next().unwrap_or(start);
+                    match text {
+                        Some(text) => transcript.push(text, end),
+                        None => transcript.push_audio(end - start),
+                    }
+                    start = end;
+                }
+            }
+        }
+        let duration = mp3::duration(&audio);
+
+        let chapters: Vec<ChapterFrame> = transcript
+            .chapter_ranges()
+            .into_iter()
+            .map(|(chapter, end)| ChapterFrame {
+                title: &chapter.title,
+                start: (chapter.start * 1000.0).round() as u32,
+                end: (end * 1000.0).round() as u32,
+            })
+            .collect();
+        let mut tag = self.tag.clone();
+        tag.chapters(&chapters);
+        if tag.is_empty() {
+            return (audio, transcript, duration);
+        }
+
+        let mut tagged = BytesMut::from(tag.to_bytes());
+        tagged.extend_from_slice(&audio);
+        (tagged.freeze(), transcript, duration)
+    }
+}
+
+/// Store generated audio with its transcript and add it to the cache index
+async fn store_podcast(
+    key: &str,
+    recording: Recording,
+    processing: &PostProcessing,
+    text_content: &str,
+    feed_url: &str,
+    voice: &str,
+    index: &CacheIndex,
+    storage: &AudioStorage,
+) -> Result<()> {
+    let processing = *processing;
+    let (podcast, transcript, duration) =
+        tokio::task::spawn_blocking(move || recording.master(&processing))
+            .await
+            .map_err(|e| Error::from_string(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    let size = podcast.len() as u64;
+
+    storage
+        .write(key, podcast)
+        .await
+        .map_err(|e| Error::from_string(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    // The episode is usable without transcript
+    let stored_transcript = match serde_json::to_vec(&transcript) {
+        Ok(transcript) => {
+            let size = transcript.len() as u64;
+            storage
+                .write(&get_transcript_key(key), Bytes::from(transcript))
+                .await
+                .map(|_| size)
+                .map_err(|e| e.to_string())
+        }
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = &stored_transcript {
+        tracing::error!("Unable to store transcript of {key:?}: {e}");
+    }
+
+    let narration = Narration {
+        text_hash: format!("{:x}", Sha256::digest(text_content.as_bytes())),
+        characters: text_content.chars().count() as u64,
+        duration: Some(duration),
+        transcript: stored_transcript.ok(),
+        chapters: transcript.chapters.len(),
+    };
+    if let Err(e) = index.insert(key, size, feed_url, voice, Some(&narration)) {
+        tracing::error!("Unable to add {key:?} to cache index: {e}");
+    }
+
+    metrics::PODCAST_CACHE.with_label_values(&["miss"]).inc();
+
+    Ok(())
+}
+
+/// Whether the audio of an episode is stored already
+async fn is_stored(
+    key: &str,
+    feed_url: &str,
+    voice: &str,
+    index: &CacheIndex,
+    storage: &AudioStorage,
+) -> Result<bool> {
+    let cached = storage
+        .size(key)
+        .await
+        .map_err(|e| Error::from_string(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    let Some(size) = cached else {
+        return Ok(false);
+    };
+    metrics::PODCAST_CACHE.with_label_values(&["hit"]).inc();
+
+    // Audio could have been stored by another instance sharing the storage
+    if index.get(key).is_none()
+        && let Err(e) = index.insert(key, size, feed_url, voice, None)
+    {
+        tracing::error!("Unable to add {key:?} to cache index: {e}");
+    }
+
+    Ok(true)
+}
+
+/// Wait for a generation slot unless the audio of an episode is stored already
+/// Returns `None` if the audio is stored (the storage is checked again once the slot is acquired,
+/// the audio could have been generated while waiting)
+async fn acquire_unless_stored(
+    key: &str,
+    feed_url: &str,
+    voice: &str,
+    queue: &GenerationQueue,
+    index: &CacheIndex,
+    storage: &AudioStorage,
+) -> Result<Option<OwnedSemaphorePermit>> {
+    if is_stored(key, feed_url, voice, index, storage).await? {
+        return Ok(None);
+    }
+
+    let perm = queue.acquire().await?;
+    if is_stored(key, feed_url, voice, index, storage).await? {
+        return Ok(None);
+    }
+
+    Ok(Some(perm))
+}
+
+/// Make sure the audio of an episode is stored (generates it if missing)
+/// With `stream`, missing audio is forwarded while it is synthesized instead of waiting for it
+/// (the generation slot is held until the audio is stored), unless it is post-processed: the
+/// stored audio would differ from the streamed one
+/// With `replace`, stored audio is generated again and overwritten once the new audio is complete
 pub async fn generate_podcast(
-    file_path: &Path,
+    key: &str,
     feed_url: &str,
     entry_uid: &str,
     voice: &str,
-    ignore: &mut Vec<String>,
+    ignore: &[String],
+    preset: Option<&FeedPreset>,
+    jingles: Arc<Jingles>,
     normalize: bool,
+    stream: bool,
+    replace: bool,
     tts_api_base: &str,
-    tts_model: &str,
-    permit: &Semaphore,
-) -> Result<(Vec<u8>, bool)> {
-    let _perm = permit.acquire().await.map_err(|e| {
-        Error::from_string(
-            format!("Failed to acquire permit for podcast generation: {e}"),
-            StatusCode::INTERNAL_SERVER_ERROR,
+    tts_conf: &Feed2PodcastTTSConfig,
+    processing: &PostProcessing,
+    artworks: &Artworks,
+    queue: &GenerationQueue,
+    index: &Arc<CacheIndex>,
+    storage: &Arc<AudioStorage>,
+) -> Result<Episode> {
+    let perm = if replace {
+        queue.acquire().await?
+    } else {
+        match acquire_unless_stored(key, feed_url, voice, queue, index, storage).await? {
+            Some(perm) => perm,
+            None => return Ok(Episode::Cached),
+        }
+    };
+
+    let channel = fetch_channel(feed_url).await?;
+
+    let item = channel
+        .items()
+        .iter()
+        .find(|el| match &el.guid {
+            Some(item_guid) => item_guid.value == *entry_uid,
+            None => false,
+        })
+        .ok_or(Error::from_string(
+            format!("Item with GUI '{entry_uid}' does not exist!"),
+            StatusCode::BAD_REQUEST,
+        ))?;
+
+    let article = item.description().ok_or(Error::from_string(
+        "No content found!",
+        StatusCode::NOT_FOUND,
+    ))?;
+    let article = Article::parse(article, ignore)?;
+    let mut script = Script::article(&article, item.title.as_deref().unwrap_or_default());
+
+    let render = |template| render_template(template, &channel, item).trim().to_string();
+    if let Some(intro) = preset.and_then(|preset| preset.intro.as_deref()) {
+        script.intro(&render(intro));
+    }
+    if let Some(outro) = preset.and_then(|preset| preset.outro.as_deref()) {
+        script.outro(&render(outro));
+    }
+    let metadata = preset
+        .map(|preset| preset.podcast.clone())
+        .unwrap_or_default();
+
+    narrate(
+        key,
+        perm,
+        script,
+        tags::episode_tag(&channel, item, &metadata, voice, &tts_conf.model, artworks),
+        feed_url,
+        voice,
+        jingles,
+        normalize,
+        stream,
+        tts_api_base,
+        tts_conf,
+        processing,
+        index,
+        storage,
+    )
+    .await
+}
+
+/// Make sure the audio of a digest episode is stored (generates it if missing, see
+/// `generate_podcast`)
+pub async fn generate_digest(
+    key: &str,
+    name: &str,
+    preset: &DigestPreset,
+    period: Period,
+    jingles: Arc<Jingles>,
+    stream: bool,
+    tts_api_base: &str,
+    tts_conf: &Feed2PodcastTTSConfig,
+    processing: &PostProcessing,
+    artworks: &Artworks,
+    queue: &GenerationQueue,
+    index: &Arc<CacheIndex>,
+    storage: &Arc<AudioStorage>,
+) -> Result<Episode> {
+    let source = digest::source_id(name);
+
+    let Some(perm) =
+        acquire_unless_stored(key, &source, &preset.voice, queue, index, storage).await?
+    else {
+        return Ok(Episode::Cached);
+    };
+
+    let digest = digest::fetch(preset).await?;
+    let channel = digest.channel(preset);
+    let episode = digest.episode(&channel, period);
+    let script = digest.script(preset, &channel, &episode, period)?;
+
+    narrate(
+        key,
+        perm,
+        script,
+        tags::episode_tag(
+            &channel,
+            &episode,
+            &preset.podcast,
+            &preset.voice,
+            &tts_conf.model,
+            artworks,
+        ),
+        &source,
+        &preset.voice,
+        jingles,
+        preset.normalize,
+        stream,
+        tts_api_base,
+        tts_conf,
+        processing,
+        index,
+        storage,
+    )
+    .await
+}
+
+/// Make sure the audio of the current briefing of a feed is stored (generates it if missing, see
+/// `generate_podcast`)
+/// Once it is stored, the previous versions of the briefing with the same voice are deleted
+pub async fn generate_briefing(
+    key: &str,
+    feed_url: &str,
+    briefing: Briefing,
+    metadata: &PodcastMetadata,
+    voice: &str,
+    jingles: Arc<Jingles>,
+    normalize: bool,
+    stream: bool,
+    tts_api_base: &str,
+    tts_conf: &Feed2PodcastTTSConfig,
+    processing: &PostProcessing,
+    artworks: &Artworks,
+    queue: &GenerationQueue,
+    index: &Arc<CacheIndex>,
+    storage: &Arc<AudioStorage>,
+) -> Result<Episode> {
+    let source = briefing::source_id(feed_url)?;
+
+    let Some(perm) = acquire_unless_stored(key, &source, voice, queue, index, storage).await?
+    else {
+        return Ok(Episode::Cached);
+    };
+
+    let Briefing {
+        channel,
+        episode,
+        script,
+        ..
+    } = briefing;
+
+    let episode = narrate(
+        key,
+        perm,
+        script,
+        tags::episode_tag(
+            &channel,
+            &episode,
+            metadata,
+            voice,
+            &tts_conf.model,
+            artworks,
+        ),
+        &source,
+        voice,
+        jingles,
+        normalize,
+        stream,
+        tts_api_base,
+        tts_conf,
+        processing,
+        index,
+        storage,
+    )
+    .await?;
+
+    let (key, voice, index, storage) = (
+        String::from(key),
+        String::from(voice),
+        index.clone(),
+        storage.clone(),
+    );
+    Ok(match episode {
+        Episode::Streaming { body, stored } => Episode::Streaming {
+            body,
+            stored: tokio::spawn(async move {
+                let stored = stored.await.unwrap_or(false);
+                if stored {
+                    remove_replaced_briefings(&key, &source, &voice, &index, &storage).await;
+                }
+                stored
+            }),
+        },
+        episode => {
+            remove_replaced_briefings(&key, &source, &voice, &index, &storage).await;
+            episode
+        }
+    })
+}
+
+/// Delete the versions of a briefing other than the stored one (`key`) with the same voice
+async fn remove_replaced_briefings(
+    key: &str,
+    source: &str,
+    voice: &str,
+    index: &CacheIndex,
+    storage: &AudioStorage,
+) {
+    let replaced: Vec<_> = index
+        .entries()
+        .into_iter()
+        .filter(|entry| {
+            entry.path != key
+                && entry.is_in_feed(source)
+                && entry.path.ends_with(&format!("/{voice}.mp3"))
+        })
+        .collect();
+
+    if !replaced.is_empty() {
+        let report = CleanupReport::delete(index, storage, &replaced).await;
+        tracing::info!("Removed {} replaced versions of {source}", report.files);
+    }
+}
+
+/// Forward audio to a streaming client
+/// Audio is buffered while the client lags behind, so synthesis (holding the generation slot)
+/// never waits for it, a disconnected client is ignored (the audio is stored anyway)
+fn forward(tx: &mpsc::UnboundedSender<std::io::Result<Bytes>>, audio: Bytes) {
+    if !audio.is_empty() {
+        let _ = tx.unbounded_send(Ok(audio));
+    }
+}
+
+/// Synthesize and store the audio of a script (see `generate_podcast`)
+async fn narrate(
+    key: &str,
+    perm: OwnedSemaphorePermit,
+    script: Script,
+    tag: impl Future<Output = Tag>,
+    feed_url: &str,
+    voice: &str,
+    jingles: Arc<Jingles>,
+    normalize: bool,
+    stream: bool,
+    tts_api_base: &str,
+    tts_conf: &Feed2PodcastTTSConfig,
+    processing: &PostProcessing,
+    index: &Arc<CacheIndex>,
+    storage: &Arc<AudioStorage>,
+) -> Result<Episode> {
+    if script.chunks.is_empty() {
+        return Err(Error::from_string(
+            "No content found!",
+            StatusCode::NOT_FOUND,
+        ));
+    }
+
+    let text_content = script.text();
+    let Script { chunks, chapters } = script;
+
+    tracing::debug!("Narrating {key:?}:\n{text_content}");
+
+    if !stream || processing.is_enabled() {
+        let tag = tag.await;
+        let mut recording = Recording::new(tag, chapters, jingles);
+        for chunk in &chunks {
+            recording.start_chunk();
+            let audio = tts::speech(chunk, voice, Some(normalize), tts_api_base, tts_conf).await?;
+            recording.push(&audio);
+            recording.end_chunk(chunk);
+        }
+        recording.finish();
+
+        store_podcast(
+            key,
+            recording,
+            processing,
+            &text_content,
+            feed_url,
+            voice,
+            index,
+            storage,
         )
-    })?;
+        .await?;
 
-    Ok(if !file_path.exists() {
-        let content = reqwest::get(feed_url)
-            .await
-            .map_err(|e| {
-                Error::from_string(
-                    format!("Unable to fetch original feed: {e}"),
-                    StatusCode::BAD_REQUEST,
-                )
-            })?
-            .bytes()
-            .await
-            .map_err(|_| Error::from_string("Invalid feed content!", StatusCode::BAD_REQUEST))?;
+        return Ok(Episode::Generated);
+    }
 
-        let channel = Channel::read_from(&content[..]).map_err(|e| {
-            Error::from_string(
-                format!("Unable to parse feed: {e}"),
-                StatusCode::BAD_REQUEST,
-            )
-        })?;
+    // The first chunk is requested before responding, so failing requests are reported
+    let (first, tag) = tokio::join!(
+        tts::speech_stream(&chunks[0], voice, Some(normalize), tts_api_base, tts_conf),
+        tag
+    );
+    let first = first?;
 
-        let item = channel
-            .items
-            .into_iter()
-            .find(|el| match &el.guid {
-                Some(item_guid) => item_guid.value == *entry_uid,
-                None => false,
-            })
-            .ok_or(Error::from_string(
-                format!("Item with GUI '{entry_uid}' does not exist!"),
-                StatusCode::BAD_REQUEST,
-            ))?;
+    let (tx, rx) = mpsc::unbounded::<std::io::Result<Bytes>>();
+    let (key, feed_url, voice, tts_api_base, tts_conf) = (
+        String::from(key),
+        String::from(feed_url),
+        String::from(voice),
+        String::from(tts_api_base),
+        tts_conf.clone(),
+    );
+    let (processing, index, storage) = (*processing, index.clone(), storage.clone());
 
-        let mut text_content: String;
+    // Synthesis continues if the client disconnects, so the audio is stored anyway
+    let stored = tokio::spawn(async move {
+        let _perm = perm;
 
-        // Required to fix: rustc: future is not `Send` as this value is used across an await
-        {
-            let article = item.description.ok_or(Error::from_string(
-                "No content found!",
-                StatusCode::NOT_FOUND,
-            ))?;
-
-            let doc = scraper::Html::parse_document(&article);
-
-            // Extract and append main article body
-            text_content = doc.root_element().text().collect();
-
-            // Remove ignored elements content
-            ignore.extend([String::from("style"), String::from("script")]);
-            for ignore_tag in ignore {
-                let ignore_selector =
-                    scraper::Selector::parse(ignore_tag.as_str()).map_err(|e| {
-                        Error::from_string(
-                            format!("Invalid selector '{}': {}", ignore_tag, e),
-                            StatusCode::BAD_REQUEST,
-                        )
-                    })?;
-
-                for element_to_ignore in doc.select(&ignore_selector) {
-                    let text_to_ignore = element_to_ignore.text().collect::<String>();
-
-                    text_content = text_content.replace(text_to_ignore.trim(), "");
+        // Chapters are only known once the audio is complete, so the streamed tag lacks them
+        if !tag.is_empty() {
+            forward(&tx, tag.to_bytes());
+        }
+
+        let mut recording = Recording::new(tag, chapters, jingles);
+        let mut speech = Some(first);
+        for chunk in &chunks {
+            forward(&tx, recording.start_chunk());
+            let synthesized = match speech.take() {
+                Some(speech) => Ok(speech),
+                None => {
+                    tts::speech_stream(chunk, &voice, Some(normalize), &tts_api_base, &tts_conf)
+                        .await
                 }
+            };
+            let forwarded = match synthesized {
+                Ok(mut speech) => loop {
+                    match speech.chunk().await {
+                        Ok(Some(data)) => forward(&tx, recording.push(&data)),
+                        Ok(None) => break Ok(()),
+                        Err(e) => break Err(e),
+                    }
+                },
+                Err(e) => Err(e),
+            };
+
+            if let Err(e) = forwarded {
+                tracing::error!("Streaming {key:?} failed: {e}");
+                let _ = tx.unbounded_send(Err(std::io::Error::other(e.to_string())));
+                return false;
             }
+            recording.end_chunk(chunk);
         }
+        forward(&tx, recording.finish());
+        drop(tx);
 
-        println!("{}", &text_content);
-
-        let client = reqwest::Client::new();
-        let tts_req_body = json!({ "input": text_content, "model": tts_model, "voice": voice, "normalization_options": { "normalize": normalize}});
-        let podcast = client
-            .post(format!("{}/audio/speech", tts_api_base))
-            .body(tts_req_body.to_string())
-            .send()
-            .await
-            .map_err(|e| {
-                Error::from_string(
-                    format!("Unable to get response from TTS Server: {e}"),
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                )
-            })?
-            .error_for_status()
-            .map_err(|e| {
-                Error::from_string(
-                    format!("Unable to get response from TTS Server: {e}"),
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                )
-            })?
-            .bytes()
-            .await
-            .map_err(|e| {
-                Error::from_string(
-                    format!("Failed to read TTS response body: {e}"),
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                )
-            })?;
-
-        std::fs::write(file_path, &podcast).map_err(|e| {
-            Error::from_string(
-                format!("Failed to write audio file: {e}"),
-                StatusCode::INTERNAL_SERVER_ERROR,
-            )
-        })?;
-
-        (podcast.into(), true)
-    } else {
-        (
-            std::fs::read(file_path).map_err(|e| {
-                Error::from_string(
-                    format!("Failed to read audio file: {e}"),
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                )
-            })?,
-            false,
+        match store_podcast(
+            &key,
+            recording,
+            &processing,
+            &text_content,
+            &feed_url,
+            &voice,
+            &index,
+            &storage,
         )
+        .await
+        {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::error!("Unable to store streamed {key:?}: {e}");
+                false
+            }
+        }
+    });
+
+    Ok(Episode::Streaming {
+        body: Body::from_bytes_stream(rx),
+        stored,
     })
 }
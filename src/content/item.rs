@@ -0,0 +1,277 @@
+use rss::{Channel, Item};
+
+use crate::{config::PodcastMetadata, feed::channel_extension};
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Publication date of an item (time in the zone of the feed)
+pub struct PublicationDate {
+    pub year: u32,
+
+    /// Month (1 - 12)
+    pub month: u32,
+
+    pub day: u32,
+
+    /// Hour and minute
+    pub time: Option<(u32, u32)>,
+
+    /// Offset of the time zone to UTC in minutes (e.g. `-300` for `-0500`)
+    pub offset: i32,
+}
+
+impl PublicationDate {
+    /// Date of the given day since 1970-01-01 (without time)
+    pub fn from_days(days: i64) -> Self {
+        // Civil calendar conversion of eras with 400 years (146097 days)
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month + 2) / 5 + 1;
+        let month = if month < 10 { month + 3 } else { month - 9 };
+
+        Self {
+            year: (year_of_era + era * 400 + i64::from(month <= 2)) as u32,
+            month: month as u32,
+            day: day as u32,
+            time: None,
+            offset: 0,
+        }
+    }
+
+    /// Days since 1970-01-01
+    pub fn days(&self) -> i64 {
+        let year = self.year as i64 - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let month = self.month as i64;
+        let day_of_year =
+            (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    /// Date as used in RSS feeds (e.g. `Tue, 10 Jun 2003 04:00:00 GMT` or
+    /// `Tue, 10 Jun 2003 04:00:00 +0200`)
+    pub fn rfc2822(&self) -> String {
+        let (hour, minute) = self.time.unwrap_or_default();
+        let zone = match self.offset {
+            0 => String::from("GMT"),
+            offset => format!(
+                "{}{:02}{:02}",
+                if offset < 0 { '-' } else { '+' },
+                offset.abs() / 60,
+                offset.abs() % 60
+            ),
+        };
+        format!(
+            "{}, {:02} {} {} {hour:02}:{minute:02}:00 {zone}",
+            WEEKDAYS[(self.days() + 3).rem_euclid(7) as usize],
+            self.day,
+            &MONTHS[self.month as usize - 1][..3],
+            self.year
+        )
+    }
+
+    /// Date as it is spoken (e.g. `June 10, 2003`)
+    pub fn spoken(&self) -> String {
+        format!(
+            "{} {}, {}",
+            MONTHS[self.month as usize - 1],
+            self.day,
+            self.year
+        )
+    }
+}
+
+/// Hour and minute of a time starting with `HH:MM`
+fn parse_time(time: &str) -> Option<(u32, u32)> {
+    Some((time.get(..2)?.parse().ok()?, time.get(3..5)?.parse().ok()?))
+}
+
+/// Offset of a time zone in minutes, either numeric (`+0200`, `-05:00`) or one of the zone names
+/// allowed by RFC 2822 (`GMT`, `EST`, ...), unknown zones are treated as UTC
+fn parse_offset(zone: &str) -> i32 {
+    if let Some(sign) = zone.chars().next().filter(|c| matches!(c, '+' | '-')) {
+        let digits = zone[1..].replace(':', "");
+        let hours: i32 = digits
+            .get(..2)
+            .and_then(|h| h.parse().ok())
+            .unwrap_or_default();
+        let minutes: i32 = digits
+            .get(2..4)
+            .and_then(|m| m.parse().ok())
+            .unwrap_or_default();
+        let offset = hours * 60 + minutes;
+        return if sign == '-' { -offset } else { offset };
+    }
+
+    60 * match zone.to_uppercase().as_str() {
+        "EDT" => -4,
+        "EST" | "CDT" => -5,
+        "CST" | "MDT" => -6,
+        "MST" | "PDT" => -7,
+        "PST" => -8,
+        _ => 0,
+    }
+}
+
+/// Publication date of an item, parsed from `pubDate` (RFC 2822) or `dc:date` (ISO 8601)
+pub fn publication_date(item: &Item) -> Option<PublicationDate> {
+    if let Some(pub_date) = item.pub_date() {
+        // e.g. `Tue, 10 Jun 2003 04:00:00 GMT` (the weekday is optional)
+        let mut parts = pub_date
+            .split_whitespace()
+            .skip_while(|part| part.ends_with(','));
+        let day = parts.next()?.parse().ok()?;
+        let month = parts.next()?.to_lowercase();
+        let month = MONTHS
+            .iter()
+            .position(|m| month.starts_with(&m[..3].to_lowercase()))? as u32
+            + 1;
+        let year = parts.next().filter(|year| year.len() == 4)?.parse().ok()?;
+        let time = parts.next().and_then(parse_time);
+        let offset = parts.next().map(parse_offset).unwrap_or_default();
+
+        return Some(PublicationDate {
+            year,
+            month,
+            day,
+            time,
+            offset,
+        });
+    }
+
+    // e.g. `2003-06-10T04:00:00Z` or `2003-06-10T04:00:00.000+02:00`
+    let date = item.dublin_core_ext()?.dates().first()?;
+    let offset = date
+        .get(11..)
+        .and_then(|time| time.find(['+', '-']).map(|start| &time[start..]))
+        .map(parse_offset)
+        .unwrap_or_default();
+    Some(PublicationDate {
+        year: date.get(..4)?.parse().ok()?,
+        month: date
+            .get(5..7)?
+            .parse()
+            .ok()
+            .filter(|m| (1..=12).contains(m))?,
+        day: date.get(8..10)?.parse().ok()?,
+        time: date.get(11..).and_then(parse_time),
+        offset,
+    })
+}
+
+/// Name of an RSS author (`jane@example.com (Jane Doe)` is reduced to `Jane Doe`)
+fn author_name(author: &str) -> &str {
+    author
+        .split_once('(')
+        .and_then(|(_, name)| name.strip_suffix(')'))
+        .unwrap_or(author)
+}
+
+/// Author given by an item itself
+pub fn item_author(item: &Item) -> Option<String> {
+    item.itunes_ext()
+        .and_then(|ext| ext.author())
+        .or(item
+            .dublin_core_ext()
+            .and_then(|dc| dc.creators().first())
+            .map(String::as_str))
+        .or(item.author().map(author_name))
+        .map(String::from)
+}
+
+/// Author of an item, falls back to the author of the feed
+pub fn author(channel: &Channel, item: &Item) -> String {
+    item_author(item)
+        .or(channel_extension(channel, &PodcastMetadata::default()).author)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: u32, month: u32, day: u32) -> PublicationDate {
+        PublicationDate {
+            year,
+            month,
+            day,
+            time: None,
+            offset: 0,
+        }
+    }
+
+    fn ymd(date: &PublicationDate) -> (u32, u32, u32) {
+        (date.year, date.month, date.day)
+    }
+
+    #[test]
+    fn converts_days() {
+        for (days, expected) in [
+            (0, (1970, 1, 1)),
+            (-1, (1969, 12, 31)),
+            (11_017, (2000, 3, 1)),
+            (19_782, (2024, 2, 29)),
+            (47_541, (2100, 3, 1)),
+        ] {
+            assert_eq!(ymd(&PublicationDate::from_days(days)), expected);
+            assert_eq!(date(expected.0, expected.1, expected.2).days(), days);
+        }
+
+        for days in -1000..30_000 {
+            assert_eq!(PublicationDate::from_days(days).days(), days);
+        }
+    }
+
+    #[test]
+    fn formats_rfc2822() {
+        let mut date = date(2024, 6, 10);
+        date.time = Some((4, 5));
+        assert_eq!(date.rfc2822(), "Mon, 10 Jun 2024 04:05:00 GMT");
+
+        date.offset = 120;
+        assert_eq!(date.rfc2822(), "Mon, 10 Jun 2024 04:05:00 +0200");
+
+        date.offset = -210;
+        assert_eq!(date.rfc2822(), "Mon, 10 Jun 2024 04:05:00 -0330");
+    }
+
+    #[test]
+    fn parses_time_zones() {
+        assert_eq!(parse_offset("GMT"), 0);
+        assert_eq!(parse_offset("+0200"), 120);
+        assert_eq!(parse_offset("-05:30"), -330);
+        assert_eq!(parse_offset("EST"), -300);
+        assert_eq!(parse_offset("pdt"), -420);
+
+        let item = Item {
+            pub_date: Some(String::from("Tue, 10 Jun 2003 04:00:00 -0500")),
+            ..Default::default()
+        };
+        let date = publication_date(&item).unwrap();
+        assert_eq!((ymd(&date), date.time), ((2003, 6, 10), Some((4, 0))));
+        assert_eq!(date.rfc2822(), "Tue, 10 Jun 2003 04:00:00 -0500");
+    }
+}
@@ -1,4 +1,22 @@
 mod routes;
 pub use routes::Router;
 
+mod article;
+pub use article::Article;
+
+mod template;
+pub use template::render_template;
+
+mod script;
+pub use script::Script;
+
 mod generate;
+pub use generate::generate_podcast;
+
+mod item;
+pub use item::{PublicationDate, publication_date};
+
+mod range;
+
+mod tags;
+pub use tags::{Artworks, MAX_ARTWORKS};
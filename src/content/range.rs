@@ -0,0 +1,215 @@
+use std::{
+    ops::Range,
+    time::{Duration, SystemTime},
+};
+
+use crate::cache::CacheEntry;
+
+/// Validators of a cached episode used for conditional requests
+pub struct Validators {
+    pub etag: String,
+    pub last_modified: SystemTime,
+}
+
+impl Validators {
+    /// Validators change whenever the episode is (re)generated
+    pub fn new(entry: &CacheEntry) -> Self {
+        Self {
+            etag: format!("\"{:x}-{:x}\"", entry.created, entry.size),
+            last_modified: SystemTime::UNIX_EPOCH + Duration::from_secs(entry.created),
+        }
+    }
+
+    /// `Last-Modified` header value
+    pub fn last_modified(&self) -> String {
+        httpdate::fmt_http_date(self.last_modified)
+    }
+
+    /// Whether the copy of the client is still valid (`If-None-Match`, `If-Modified-Since`)
+    /// `If-Modified-Since` is ignored if `If-None-Match` is present
+    pub fn not_modified(
+        &self,
+        if_none_match: Option<&str>,
+        if_modified_since: Option<&str>,
+    ) -> bool {
+        match (if_none_match, if_modified_since) {
+            (Some(if_none_match), _) => if_none_match.split(',').any(|tag| {
+                let tag = tag.trim();
+                tag == "*" || tag.trim_start_matches("W/") == self.etag
+            }),
+            (None, Some(since)) => {
+                httpdate::parse_http_date(since).is_ok_and(|since| self.last_modified <= since)
+            }
+            (None, None) => false,
+        }
+    }
+
+    /// Whether a range may be served (`If-Range` is missing or matches)
+    pub fn range_applies(&self, if_range: Option<&str>) -> bool {
+        match if_range {
+            None => true,
+            Some(if_range) if if_range.starts_with('"') => if_range == self.etag,
+            Some(if_range) => {
+                httpdate::parse_http_date(if_range).is_ok_and(|date| date == self.last_modified)
+            }
+        }
+    }
+}
+
+/// Requested part of a file
+#[derive(Debug)]
+pub enum RangeRequest {
+    /// The whole file (no or unsupported `Range` header)
+    Full,
+
+    /// Bytes of the file (end is exclusive)
+    Partial(Range<u64>),
+
+    /// The range lies outside of the file
+    Unsatisfiable,
+}
+
+/// Parse a `Range` header for a file of the given size
+/// Only single byte ranges are supported, other ranges are ignored (the whole file is served)
+pub fn parse_range(header: Option<&str>, size: u64) -> RangeRequest {
+    let Some(spec) = header.and_then(|header| header.trim().strip_prefix("bytes=")) else {
+        return RangeRequest::Full;
+    };
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RangeRequest::Full;
+    };
+
+    let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+        // bytes=<start>-<end>
+        (Ok(start), Ok(end)) if start <= end => start..end.saturating_add(1).min(size),
+        // bytes=<start>-
+        (Ok(start), Err(_)) if end.is_empty() => start..size,
+        // bytes=-<suffix length>
+        (Err(_), Ok(suffix)) if start.is_empty() => size.saturating_sub(suffix)..size,
+        _ => return RangeRequest::Full,
+    };
+
+    if range.start >= size || range.is_empty() {
+        RangeRequest::Unsatisfiable
+    } else {
+        RangeRequest::Partial(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validators() -> Validators {
+        Validators::new(&CacheEntry {
+            path: String::from("example.com/feed.xml/1/alloy.mp3"),
+            size: 0x1000,
+            created: 1_700_000_000,
+            last_access: 1_700_000_000,
+            feed: None,
+            voice: None,
+            text_hash: None,
+            characters: None,
+            duration: None,
+            transcript: false,
+            transcript_size: 0,
+            chapters: 0,
+            pinned: false,
+        })
+    }
+
+    fn partial(header: &str, size: u64) -> Option<Range<u64>> {
+        match parse_range(Some(header), size) {
+            RangeRequest::Partial(range) => Some(range),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn parses_byte_ranges() {
+        assert_eq!(partial("bytes=0-99", 1000), Some(0..100));
+        assert_eq!(partial("bytes=500-", 1000), Some(500..1000));
+        assert_eq!(partial("bytes=-100", 1000), Some(900..1000));
+        assert_eq!(partial("bytes=-2000", 1000), Some(0..1000));
+        assert_eq!(partial("bytes=900-2000", 1000), Some(900..1000));
+        assert_eq!(
+            partial(&format!("bytes=0-{}", u64::MAX), 1000),
+            Some(0..1000)
+        );
+    }
+
+    #[test]
+    fn rejects_unsatisfiable_ranges() {
+        assert!(matches!(
+            parse_range(Some("bytes=1000-"), 1000),
+            RangeRequest::Unsatisfiable
+        ));
+        assert!(matches!(
+            parse_range(Some("bytes=-0"), 1000),
+            RangeRequest::Unsatisfiable
+        ));
+        assert!(matches!(
+            parse_range(Some("bytes=0-0"), 0),
+            RangeRequest::Unsatisfiable
+        ));
+    }
+
+    #[test]
+    fn ignores_unsupported_ranges() {
+        for header in [
+            "bytes=0-1,5-6",
+            "bytes=5-1",
+            "items=0-1",
+            "bytes=a-b",
+            "bytes=1",
+        ] {
+            assert!(
+                matches!(parse_range(Some(header), 1000), RangeRequest::Full),
+                "{header}"
+            );
+        }
+        assert!(matches!(parse_range(None, 1000), RangeRequest::Full));
+    }
+
+    #[test]
+    fn matches_etags() {
+        let validators = validators();
+        assert_eq!(validators.etag, "\"6553f100-1000\"");
+
+        assert!(validators.not_modified(Some("\"6553f100-1000\""), None));
+        assert!(validators.not_modified(Some("W/\"6553f100-1000\""), None));
+        assert!(validators.not_modified(Some("\"other\", \"6553f100-1000\""), None));
+        assert!(validators.not_modified(Some("*"), None));
+        assert!(!validators.not_modified(Some("\"other\""), None));
+    }
+
+    #[test]
+    fn compares_modification_dates() {
+        let validators = validators();
+        let last_modified = validators.last_modified();
+        assert_eq!(last_modified, "Tue, 14 Nov 2023 22:13:20 GMT");
+
+        assert!(validators.not_modified(None, Some(&last_modified)));
+        assert!(validators.not_modified(None, Some("Wed, 15 Nov 2023 00:00:00 GMT")));
+        assert!(!validators.not_modified(None, Some("Tue, 14 Nov 2023 00:00:00 GMT")));
+        assert!(!validators.not_modified(None, Some("invalid")));
+        assert!(!validators.not_modified(None, None));
+
+        // If-None-Match takes precedence
+        assert!(!validators.not_modified(Some("\"other\""), Some(&last_modified)));
+    }
+
+    #[test]
+    fn checks_if_range() {
+        let validators = validators();
+
+        assert!(validators.range_applies(None));
+        assert!(validators.range_applies(Some("\"6553f100-1000\"")));
+        assert!(validators.range_applies(Some(&validators.last_modified())));
+        assert!(!validators.range_applies(Some("\"other\"")));
+        assert!(!validators.range_applies(Some("Wed, 15 Nov 2023 00:00:00 GMT")));
+    }
+}
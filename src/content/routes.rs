@@ -1,20 +1,244 @@
 use std::sync::Arc;
 
-use poem::{Result, web::Data};
+use poem::{Error, Result, web::Data};
 use poem_openapi::{
     OpenApi,
-    param::{Path, Query},
+    param::{Header, Path, Query},
     payload::Binary,
 };
-use tokio::sync::Semaphore;
+use reqwest::StatusCode;
 
 use crate::{
-    cache,
-    content::generate::generate_podcast,
-    data::{Feed2PodcastDirs, Feed2PodcastTTSConfig, Feed2PodcastURLs},
-    schemas::{CategoryTags, DownloadFileResponse},
+    audio::process::PostProcessing,
+    briefing,
+    cache::{self, AudioStorage, CacheIndex},
+    content::{
+        Artworks,
+        generate::{Episode, generate_briefing, generate_digest, generate_podcast},
+        range::{RangeRequest, Validators, parse_range},
+    },
+    data::{Feed2PodcastPresets, Feed2PodcastTTSConfig, Feed2PodcastURLs},
+    digest::{self, Period},
+    metrics,
+    queue::GenerationQueue,
+    schemas::{AudioHeadResponse, CategoryTags, DownloadFileResponse},
 };
 
+const ACCEPT_RANGES: &str = "bytes";
+
+/// Size and validators of a stored episode (`None` if it is not stored)
+/// Validators are missing if the episode is not indexed
+async fn stored_episode(
+    key: &str,
+    cache_index: &CacheIndex,
+    storage: &AudioStorage,
+) -> Result<Option<(u64, Option<Validators>)>> {
+    if let Some(entry) = cache_index.get(key) {
+        return Ok(Some((entry.size, Some(Validators::new(&entry)))));
+    }
+
+    Ok(storage
+        .size(key)
+        .await
+        .map_err(|e| Error::from_string(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+        .map(|size| (size, None)))
+}
+
+/// Redirect to the storage if it serves cached audio directly (presigned URLs)
+async fn redirect_to_storage(
+    key: &str,
+    cache_index: &CacheIndex,
+    storage: &AudioStorage,
+) -> Result<Option<DownloadFileResponse>> {
+    if cache_index.get(key).is_none() {
+        return Ok(None);
+    }
+    let Some(location) = storage
+        .presigned_url(key)
+        .await
+        .map_err(|e| Error::from_string(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+    else {
+        return Ok(None);
+    };
+
+    // Another instance sharing the storage could have removed the audio by its cleanup
+    let stored = storage
+        .size(key)
+        .await
+        .map_err(|e| Error::from_string(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+    if stored.is_none() {
+        if let Err(e) = cache_index.remove(key) {
+            tracing::error!("{e}");
+        }
+        return Ok(None);
+    }
+
+    metrics::PODCAST_CACHE.with_label_values(&["hit"]).inc();
+    cache_index.touch(key);
+
+    Ok(Some(DownloadFileResponse::Redirect(location)))
+}
+
+/// Rejects versions of briefings which the briefing feed can't contain (they would become part
+/// of the cache key)
+fn invalid_version() -> Error {
+    Error::from_string("Invalid version of a briefing", StatusCode::BAD_REQUEST)
+}
+
+/// Size and validators of stored audio (without generating missing audio)
+async fn head_response(
+    key: &str,
+    cache_index: &CacheIndex,
+    storage: &AudioStorage,
+    if_none_match: Option<String>,
+    if_modified_since: Option<String>,
+) -> Result<AudioHeadResponse> {
+    let Some((size, validators)) = stored_episode(key, cache_index, storage).await? else {
+        return Ok(AudioHeadResponse::Audio(
+            String::from("audio/mpeg"),
+            None,
+            String::from(ACCEPT_RANGES),
+            None,
+            None,
+        ));
+    };
+    let etag = validators.as_ref().map(|v| v.etag.clone());
+    let last_modified = validators.as_ref().map(|v| v.last_modified());
+
+    if validators
+        .as_ref()
+        .is_some_and(|v| v.not_modified(if_none_match.as_deref(), if_modified_since.as_deref()))
+    {
+        return Ok(AudioHeadResponse::NotModified(etag, last_modified));
+    }
+
+    Ok(AudioHeadResponse::Audio(
+        String::from("audio/mpeg"),
+        Some(size),
+        String::from(ACCEPT_RANGES),
+        etag,
+        last_modified,
+    ))
+}
+
+/// Serve the audio of an episode (streamed or stored)
+/// Runs a cache cleanup in background if the episode was generated
+async fn episode_response(
+    key: &str,
+    episode: Episode,
+    cache_cleanup: &cache::CleanupPolicy,
+    cache_index: &Arc<CacheIndex>,
+    storage: &Arc<AudioStorage>,
+    range: Option<String>,
+    if_range: Option<String>,
+    if_none_match: Option<String>,
+    if_modified_since: Option<String>,
+) -> Result<DownloadFileResponse> {
+    let was_generated = match episode {
+        Episode::Cached => false,
+        Episode::Generated => true,
+        // Streamed audio is served without length, ranges and validators (see `Episode`)
+        Episode::Streaming { body, stored } => {
+            if !cache_cleanup.is_empty() {
+                let (cache_index, storage, cache_cleanup) =
+                    (cache_index.clone(), storage.clone(), cache_cleanup.clone());
+
+                // Run cache cleanup in background once the audio is stored
+                tokio::spawn(async move {
+                    if stored.await.unwrap_or(false) {
+                        cache::run_cleanup_task(cache_index, storage, cache_cleanup).await;
+                    }
+                });
+            }
+
+            return Ok(DownloadFileResponse::Audio(
+                Binary(body),
+                String::from("audio/mpeg"),
+                None,
+                None,
+                None,
+                None,
+            ));
+        }
+    };
+
+    cache_index.touch(key);
+
+    if was_generated && !cache_cleanup.is_empty() {
+        // Run cache cleanup in background
+        tokio::spawn(cache::run_cleanup_task(
+            cache_index.clone(),
+            storage.clone(),
+            cache_cleanup.clone(),
+        ));
+    }
+
+    let (size, validators) =
+        stored_episode(key, cache_index, storage)
+            .await?
+            .ok_or(Error::from_string(
+                "Episode was removed from the cache, please retry",
+                StatusCode::SERVICE_UNAVAILABLE,
+            ))?;
+    let etag = validators.as_ref().map(|v| v.etag.clone());
+    let last_modified = validators.as_ref().map(|v| v.last_modified());
+
+    if validators
+        .as_ref()
+        .is_some_and(|v| v.not_modified(if_none_match.as_deref(), if_modified_since.as_deref()))
+    {
+        return Ok(DownloadFileResponse::NotModified(etag, last_modified));
+    }
+
+    let range = if validators
+        .as_ref()
+        .is_none_or(|v| v.range_applies(if_range.as_deref()))
+    {
+        parse_range(range.as_deref(), size)
+    } else {
+        RangeRequest::Full
+    };
+
+    let requested = match &range {
+        RangeRequest::Full => None,
+        RangeRequest::Partial(range) => Some(range.clone()),
+        RangeRequest::Unsatisfiable => {
+            return Ok(DownloadFileResponse::RangeNotSatisfiable(format!(
+                "bytes */{size}"
+            )));
+        }
+    };
+
+    let audio = storage
+        .open(key, requested)
+        .await
+        .map_err(|e| Error::from_string(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or(Error::from_string(
+            "Episode was removed from the cache, please retry",
+            StatusCode::SERVICE_UNAVAILABLE,
+        ))?;
+
+    Ok(match range {
+        RangeRequest::Partial(range) => DownloadFileResponse::PartialAudio(
+            Binary(audio.body),
+            String::from("audio/mpeg"),
+            audio.size,
+            format!("bytes {}-{}/{size}", range.start, range.end - 1),
+            String::from(ACCEPT_RANGES),
+            etag,
+            last_modified,
+        ),
+        _ => DownloadFileResponse::Audio(
+            Binary(audio.body),
+            String::from("audio/mpeg"),
+            Some(audio.size),
+            Some(String::from(ACCEPT_RANGES)),
+            etag,
+            last_modified,
+        ),
+    })
+}
+
 pub struct Router;
 
 #[OpenApi(prefix_path = "content", tag = "CategoryTags::Feed")]
@@ -25,10 +249,14 @@ impl Router {
     async fn get_podcast_audio(
         &self,
         Data(app_urls): Data<&Feed2PodcastURLs>,
-        Data(app_dirs): Data<&Feed2PodcastDirs>,
-        Data(cache_cleanup): Data<&cache::CleanupMethod>,
+        Data(cache_cleanup): Data<&cache::CleanupPolicy>,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+        Data(storage): Data<&Arc<AudioStorage>>,
         Data(tts_conf): Data<&Feed2PodcastTTSConfig>,
-        Data(permit): Data<&Arc<Semaphore>>,
+        Data(processing): Data<&PostProcessing>,
+        Data(artworks): Data<&Arc<Artworks>>,
+        Data(queue): Data<&Arc<GenerationQueue>>,
+        Data(presets): Data<&Feed2PodcastPresets>,
 
         /// The voice to use for the podcast
         Path(voice): Path<String>,
@@ -40,38 +268,401 @@ impl Router {
         Query(uid): Query<String>,
 
         /// HTML elements/CSS Selectors to ignore when parsing the content
-        Query(mut ignore): Query<Vec<String>>,
+        Query(ignore): Query<Option<Vec<String>>>,
 
         /// Whether to normalize text for TTS (will improve TTS but can lead to errors when content
         /// includes long numbers)
         Query(normalize): Query<bool>,
+
+        /// Single byte range to download (e.g. `bytes=0-1023`)
+        #[oai(name = "Range")]
+        Header(range): Header<Option<String>>,
+
+        /// Only download the range if the episode is unchanged (ETag or date)
+        #[oai(name = "If-Range")]
+        Header(if_range): Header<Option<String>>,
+
+        /// ETags of a cached copy
+        #[oai(name = "If-None-Match")]
+        Header(if_none_match): Header<Option<String>>,
+
+        /// Date of a cached copy
+        #[oai(name = "If-Modified-Since")]
+        Header(if_modified_since): Header<Option<String>>,
     ) -> Result<DownloadFileResponse> {
-        let audio_path = cache::get_podcast_path(&app_dirs.cache, &url, &uid, &voice)?;
+        let key = cache::get_podcast_key(&url, &uid, &voice)?;
+
+        if let Some(redirect) = redirect_to_storage(&key, cache_index, storage).await? {
+            return Ok(redirect);
+        }
 
-        let (audio, was_generated) = generate_podcast(
-            &audio_path,
+        // Intro, outro and podcast metadata of a preset for the same feed
+        let preset = presets.for_feed(&url);
+        let episode = generate_podcast(
+            &key,
             &url,
             &uid,
             &voice,
-            &mut ignore,
+            &ignore.unwrap_or_default(),
+            preset,
+            presets.jingles_for_feed(&url),
             normalize,
+            tts_conf.stream,
+            false,
+            &app_urls.tts,
+            tts_conf,
+            processing,
+            artworks,
+            queue,
+            cache_index,
+            storage,
+        )
+        .await?;
+
+        episode_response(
+            &key,
+            episode,
+            cache_cleanup,
+            cache_index,
+            storage,
+            range,
+            if_range,
+            if_none_match,
+            if_modified_since,
+        )
+        .await
+    }
+
+    /// Create Podcast audio (on demand) combining the articles of a digest published in a period
+    /// Only complete periods (listed in the digest feed) are available.
+    #[oai(path = "/digest/:name", method = "get")]
+    async fn get_digest_audio(
+        &self,
+        Data(app_urls): Data<&Feed2PodcastURLs>,
+        Data(cache_cleanup): Data<&cache::CleanupPolicy>,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+        Data(storage): Data<&Arc<AudioStorage>>,
+        Data(tts_conf): Data<&Feed2PodcastTTSConfig>,
+        Data(processing): Data<&PostProcessing>,
+        Data(artworks): Data<&Arc<Artworks>>,
+        Data(queue): Data<&Arc<GenerationQueue>>,
+        Data(presets): Data<&Feed2PodcastPresets>,
+
+        /// The name of the digest
+        Path(name): Path<String>,
+
+        /// The first day of the period (`YYYY-MM-DD`, weekly periods start on Monday)
+        Query(period): Query<String>,
+
+        /// Single byte range to download (e.g. `bytes=0-1023`)
+        #[oai(name = "Range")]
+        Header(range): Header<Option<String>>,
+
+        /// Only download the range if the episode is unchanged (ETag or date)
+        #[oai(name = "If-Range")]
+        Header(if_range): Header<Option<String>>,
+
+        /// ETags of a cached copy
+        #[oai(name = "If-None-Match")]
+        Header(if_none_match): Header<Option<String>>,
+
+        /// Date of a cached copy
+        #[oai(name = "If-Modified-Since")]
+        Header(if_modified_since): Header<Option<String>>,
+    ) -> Result<DownloadFileResponse> {
+        let (digest, jingles) = presets.digest(&name).ok_or(Error::from_string(
+            format!("Digest '{name}' does not exist!"),
+            StatusCode::NOT_FOUND,
+        ))?;
+        let period = Period::parse(digest.period, &period)
+            .filter(Period::is_complete)
+            .ok_or(Error::from_string(
+                format!("'{period}' is not the first day of a complete period"),
+                StatusCode::BAD_REQUEST,
+            ))?;
+        let key = cache::get_podcast_key(
+            &digest::source_id(&name),
+            &period.to_string(),
+            &digest.voice,
+        )?;
+
+        if let Some(redirect) = redirect_to_storage(&key, cache_index, storage).await? {
+            return Ok(redirect);
+        }
+
+        let episode = generate_digest(
+            &key,
+            &name,
+            digest,
+            period,
+            jingles,
+            tts_conf.stream,
             &app_urls.tts,
-            &tts_conf.model,
-            permit,
+            tts_conf,
+            processing,
+            artworks,
+            queue,
+            cache_index,
+            storage,
         )
         .await?;
 
-        if was_generated {
-            // Run cache cleanup in background
-            tokio::spawn(cache::run_cleanup_task(
-                app_dirs.cache.clone(),
-                cache_cleanup.clone(),
+        episode_response(
+            &key,
+            episode,
+            cache_cleanup,
+            cache_index,
+            storage,
+            range,
+            if_range,
+            if_none_match,
+            if_modified_since,
+        )
+        .await
+    }
+
+    /// Size and validators of the Podcast audio of a digest in a period
+    /// Audio which was not generated yet is not generated (the size is unknown in this case)
+    #[oai(path = "/digest/:name", method = "head")]
+    async fn head_digest_audio(
+        &self,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+        Data(storage): Data<&Arc<AudioStorage>>,
+        Data(presets): Data<&Feed2PodcastPresets>,
+
+        /// The name of the digest
+        Path(name): Path<String>,
+
+        /// The first day of the period (`YYYY-MM-DD`, weekly periods start on Monday)
+        Query(period): Query<String>,
+
+        /// ETags of a cached copy
+        #[oai(name = "If-None-Match")]
+        Header(if_none_match): Header<Option<String>>,
+
+        /// Date of a cached copy
+        #[oai(name = "If-Modified-Since")]
+        Header(if_modified_since): Header<Option<String>>,
+    ) -> Result<AudioHeadResponse> {
+        let (digest, _) = presets.digest(&name).ok_or(Error::from_string(
+            format!("Digest '{name}' does not exist!"),
+            StatusCode::NOT_FOUND,
+        ))?;
+        let period = Period::parse(digest.period, &period)
+            .filter(Period::is_complete)
+            .ok_or(Error::from_string(
+                format!("'{period}' is not the first day of a complete period"),
+                StatusCode::BAD_REQUEST,
+            ))?;
+        let key = cache::get_podcast_key(
+            &digest::source_id(&name),
+            &period.to_string(),
+            &digest.voice,
+        )?;
+
+        head_response(&key, cache_index, storage, if_none_match, if_modified_since).await
+    }
+
+    /// Create Podcast audio (on demand) reading the headlines of the latest items of a RSS Feed
+    /// A requested version is only available while it is current or cached (previous versions are
+    /// deleted once a new version is stored), otherwise the response is `410 Gone`.
+    #[oai(path = "/briefing/:voice", method = "get")]
+    async fn get_briefing_audio(
+        &self,
+        Data(app_urls): Data<&Feed2PodcastURLs>,
+        Data(cache_cleanup): Data<&cache::CleanupPolicy>,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+        Data(storage): Data<&Arc<AudioStorage>>,
+        Data(tts_conf): Data<&Feed2PodcastTTSConfig>,
+        Data(processing): Data<&PostProcessing>,
+        Data(artworks): Data<&Arc<Artworks>>,
+        Data(queue): Data<&Arc<GenerationQueue>>,
+        Data(presets): Data<&Feed2PodcastPresets>,
+
+        /// The voice to use for the podcast
+        Path(voice): Path<String>,
+
+        /// The Feed URL
+        Query(url): Query<String>,
+
+        /// Version of the briefing (from the briefing feed)
+        Query(version): Query<Option<String>>,
+
+        /// Number of items to read (defaults to 10)
+        Query(count): Query<Option<u32>>,
+
+        /// HTML elements/CSS Selectors to ignore when parsing the content
+        Query(ignore): Query<Option<Vec<String>>>,
+
+        /// Whether to normalize text for TTS (will improve TTS but can lead to errors when content
+        /// includes long numbers)
+        Query(normalize): Query<bool>,
+
+        /// Single byte range to download (e.g. `bytes=0-1023`)
+        #[oai(name = "Range")]
+        Header(range): Header<Option<String>>,
+
+        /// Only download the range if the episode is unchanged (ETag or date)
+        #[oai(name = "If-Range")]
+        Header(if_range): Header<Option<String>>,
+
+        /// ETags of a cached copy
+        #[oai(name = "If-None-Match")]
+        Header(if_none_match): Header<Option<String>>,
+
+        /// Date of a cached copy
+        #[oai(name = "If-Modified-Since")]
+        Header(if_modified_since): Header<Option<String>>,
+    ) -> Result<DownloadFileResponse> {
+        if version
+            .as_deref()
+            .is_some_and(|version| !briefing::is_version(version))
+        {
+            return Err(invalid_version());
+        }
+        let source = briefing::source_id(&url)?;
+        if let Some(version) = &version {
+            let key = cache::get_podcast_key(&source, version, &voice)?;
+            if let Some(redirect) = redirect_to_storage(&key, cache_index, storage).await? {
+                return Ok(redirect);
+            }
+            if stored_episode(&key, cache_index, storage).await?.is_some() {
+                metrics::PODCAST_CACHE.with_label_values(&["hit"]).inc();
+                return episode_response(
+                    &key,
+                    Episode::Cached,
+                    cache_cleanup,
+                    cache_index,
+                    storage,
+                    range,
+                    if_range,
+                    if_none_match,
+                    if_modified_since,
+                )
+                .await;
+            }
+        }
+
+        let briefing = briefing::fetch(
+            &url,
+            &ignore.unwrap_or_default(),
+            count.unwrap_or(briefing::DEFAULT_HEADLINES),
+        )
+        .await?;
+        if version.is_some_and(|version| version != briefing.version) {
+            return Err(Error::from_string(
+                "The version of the briefing was replaced, refresh the feed for the current one",
+                StatusCode::GONE,
             ));
         }
+        let key = cache::get_podcast_key(&source, &briefing.version, &voice)?;
 
-        Ok(DownloadFileResponse::Audio(
-            Binary(audio),
-            String::from("audio/mpeg"),
-        ))
+        let metadata = presets
+            .for_feed(&url)
+            .map(|preset| preset.podcast.clone())
+            .unwrap_or_default();
+        let episode = generate_briefing(
+            &key,
+            &url,
+            briefing,
+            &metadata,
+            &voice,
+            presets.jingles_for_feed(&url),
+            normalize,
+            tts_conf.stream,
+            &app_urls.tts,
+            tts_conf,
+            processing,
+            artworks,
+            queue,
+            cache_index,
+            storage,
+        )
+        .await?;
+
+        episode_response(
+            &key,
+            episode,
+            cache_cleanup,
+            cache_index,
+            storage,
+            range,
+            if_range,
+            if_none_match,
+            if_modified_since,
+        )
+        .await
+    }
+
+    /// Size and validators of the Podcast audio of a version of a briefing
+    /// Audio which was not generated yet is not generated (the size is unknown in this case, as
+    /// without a version)
+    #[oai(path = "/briefing/:voice", method = "head")]
+    async fn head_briefing_audio(
+        &self,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+        Data(storage): Data<&Arc<AudioStorage>>,
+
+        /// The voice to use for the podcast
+        Path(voice): Path<String>,
+
+        /// The Feed URL
+        Query(url): Query<String>,
+
+        /// Version of the briefing (from the briefing feed)
+        Query(version): Query<Option<String>>,
+
+        /// ETags of a cached copy
+        #[oai(name = "If-None-Match")]
+        Header(if_none_match): Header<Option<String>>,
+
+        /// Date of a cached copy
+        #[oai(name = "If-Modified-Since")]
+        Header(if_modified_since): Header<Option<String>>,
+    ) -> Result<AudioHeadResponse> {
+        let Some(version) = version else {
+            return Ok(AudioHeadResponse::Audio(
+                String::from("audio/mpeg"),
+                None,
+                String::from(ACCEPT_RANGES),
+                None,
+                None,
+            ));
+        };
+        if !briefing::is_version(&version) {
+            return Err(invalid_version());
+        }
+        let key = cache::get_podcast_key(&briefing::source_id(&url)?, &version, &voice)?;
+
+        head_response(&key, cache_index, storage, if_none_match, if_modified_since).await
+    }
+
+    /// Size and validators of the Podcast audio for a given article
+    /// Audio which was not generated yet is not generated (the size is unknown in this case)
+    #[oai(path = "/:voice", method = "head")]
+    async fn head_podcast_audio(
+        &self,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+        Data(storage): Data<&Arc<AudioStorage>>,
+
+        /// The voice to use for the podcast
+        Path(voice): Path<String>,
+
+        /// The Feed URL
+        Query(url): Query<String>,
+
+        /// The GUID of the article
+        Query(uid): Query<String>,
+
+        /// ETags of a cached copy
+        #[oai(name = "If-None-Match")]
+        Header(if_none_match): Header<Option<String>>,
+
+        /// Date of a cached copy
+        #[oai(name = "If-Modified-Since")]
+        Header(if_modified_since): Header<Option<String>>,
+    ) -> Result<AudioHeadResponse> {
+        let key = cache::get_podcast_key(&url, &uid, &voice)?;
+        head_response(&key, cache_index, storage, if_none_match, if_modified_since).await
     }
 }
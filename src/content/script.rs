@@ -0,0 +1,86 @@
+use super::Article;
+use crate::transcript;
+
+/// Title of the chapter preceding the first heading if the episode has no title
+const INTRODUCTION: &str = "Introduction";
+
+/// Narrated text of an episode, split into the chunks which are synthesized one by one
+#[derive(Default)]
+pub struct Script {
+    pub chunks: Vec<String>,
+
+    /// Chapters as `(index of the first chunk, title)`
+    pub chapters: Vec<(usize, String)>,
+}
+
+impl Script {
+    /// Script of an article
+    /// Every heading starts a new chunk and chapter
+    pub fn article(article: &Article, title: &str) -> Self {
+        let text = &article.text;
+        let mut sections: Vec<(usize, &str)> = article
+            .headings
+            .iter()
+            .map(|heading| (heading.offset, heading.title.as_str()))
+            .collect();
+
+        match sections.first_mut() {
+            None => {
+                return Self {
+                    chunks: transcript::split_text(text),
+                    chapters: Vec::new(),
+                };
+            }
+            Some(first) if text[..first.0].trim().is_empty() => first.0 = 0,
+            Some(_) => sections.insert(
+                0,
+                (
+                    0,
+                    if title.is_empty() {
+                        INTRODUCTION
+                    } else {
+                        title
+                    },
+                ),
+            ),
+        }
+
+        let mut script = Self::default();
+        for (nr, (start, title)) in sections.iter().enumerate() {
+            let end = sections.get(nr + 1).map_or(text.len(), |next| next.0);
+            script.push_chapter(title, &text[*start..end]);
+        }
+
+        script
+    }
+
+    /// Add a chapter narrating the given text
+    pub fn push_chapter(&mut self, title: &str, text: &str) {
+        self.chapters.push((self.chunks.len(), String::from(title)));
+        self.chunks.extend(transcript::split_text(text));
+    }
+
+    /// Add text spoken before the script, it belongs to the first chapter
+    pub fn intro(&mut self, intro: &str) {
+        if intro.is_empty() {
+            return;
+        }
+
+        self.chunks.insert(0, format!("{intro}\n\n"));
+        for (first_chunk, _) in self.chapters.iter_mut().filter(|(first, _)| *first > 0) {
+            *first_chunk += 1;
+        }
+    }
+
+    /// Add text spoken after the script, it belongs to the last chapter
+    pub fn outro(&mut self, outro: &str) {
+        if !outro.is_empty() {
+            self.chunks.push(format!("\n\n{outro}"));
+        }
+    }
+
+    /// The complete narrated text
+    pub fn text(&self) -> String {
+        self.chunks.concat()
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rss::{Channel, Item};
+
+use super::item;
+use crate::{audio::id3::Tag, config::PodcastMetadata, feed::channel_extension, lru::LruCache};
+
+/// Maximum size of artwork which is embedded into episodes (bytes)
+const MAX_ARTWORK_SIZE: usize = 2 * 1024 * 1024;
+
+/// Time to wait for the artwork, episodes are tagged without it if it takes longer
+const ARTWORK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time after which downloaded artwork is downloaded again (it could have changed)
+const ARTWORK_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Number of remembered artworks
+pub const MAX_ARTWORKS: usize = 16;
+
+/// MIME type and content of an artwork
+type Artwork = (String, Arc<Vec<u8>>);
+
+/// Downloaded artwork by URL with the time of the download
+pub type Artworks = LruCache<String, (Instant, Artwork)>;
+
+/// Artwork of the given URL, downloaded artwork is reused by all episodes of a feed
+async fn cached_artwork(artworks: &Artworks, url: &str) -> eyre::Result<Artwork> {
+    if let Some((_, artwork)) = artworks
+        .get(url)
+        .filter(|(fetched, _)| fetched.elapsed() < ARTWORK_EXPIRY)
+    {
+        return Ok(artwork);
+    }
+
+    let (mime_type, data) = fetch_artwork(url).await?;
+    let artwork = (mime_type, Arc::new(data));
+    artworks.insert(String::from(url), (Instant::now(), artwork.clone()));
+
+    Ok(artwork)
+}
+
+/// Download artwork, returns its MIME type and content
+async fn fetch_artwork(url: &str) -> eyre::Result<(String, Vec<u8>)> {
+    let response = reqwest::Client::new()
+        .get(url)
+        .timeout(ARTWORK_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let mime_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(str::trim)
+        .filter(|mime_type| mime_type.starts_with("image/"))
+        .ok_or(eyre::eyre!("Not an image"))?
+        .to_string();
+    if response
+        .content_length()
+        .is_some_and(|size| size as usize > MAX_ARTWORK_SIZE)
+    {
+        eyre::bail!("Artwork is larger than {MAX_ARTWORK_SIZE} bytes");
+    }
+
+    let data = response.bytes().await?;
+    if data.len() > MAX_ARTWORK_SIZE {
+        eyre::bail!("Artwork is larger than {MAX_ARTWORK_SIZE} bytes");
+    }
+
+    Ok((mime_type, data.to_vec()))
+}
+
+/// ID3 metadata of an episode (title, feed title as album, author, publication date, source link,
+/// TTS voice/model and artwork)
+/// `metadata` overrides the podcast values of the feed (as in the generated feed)
+pub async fn episode_tag(
+    channel: &Channel,
+    item: &Item,
+    metadata: &PodcastMetadata,
+    voice: &str,
+    model: &str,
+    artworks: &Artworks,
+) -> Tag {
+    let podcast = channel_extension(channel, metadata);
+    let item_ext = item.itunes_ext();
+
+    let mut tag = Tag::default();
+    tag.text("TIT2", item.title().unwrap_or_default());
+    tag.text("TALB", channel.title());
+    tag.text(
+        "TPE1",
+        &item::item_author(item)
+            .or(podcast.author.clone())
+            .unwrap_or_default(),
+    );
+    tag.text("TCON", "Podcast");
+
+    if let Some(date) = item::publication_date(item) {
+        tag.text("TYER", &date.year.to_string());
+        tag.text("TDAT", &format!("{:02}{:02}", date.day, date.month));
+        if let Some((hour, minute)) = date.time {
+            tag.text("TIME", &format!("{hour:02}{minute:02}"));
+        }
+    }
+
+    if let Some(link) = item.link() {
+        tag.url("WOAS", link);
+    }
+    tag.user_text("TTS Voice", voice);
+    tag.user_text("TTS Model", model);
+
+    // Episodes are tagged without artwork if it is not available
+    let artwork = item_ext.and_then(|ext| ext.image()).or(podcast.image());
+    if let Some(url) = artwork {
+        match cached_artwork(artworks, url).await {
+            Ok((mime_type, data)) => tag.picture(&mime_type, &data),
+            Err(e) => tracing::warn!("Unable to embed artwork {url:?}: {e}"),
+        }
+    }
+
+    tag
+}
@@ -0,0 +1,30 @@
+use rss::{Channel, Item};
+
+use super::item;
+
+/// Render an intro or outro template (see `config::TEMPLATE_VARIABLES`) for an item
+/// Variables without value (e.g. items without date) are replaced by an empty string
+pub fn render_template(template: &str, channel: &Channel, item: &Item) -> String {
+    let mut rendered = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{')
+        && let Some(len) = rest[start..].find('}')
+    {
+        rendered.push_str(&rest[..start]);
+        let value = match &rest[start + 1..start + len] {
+            "title" => item.title().unwrap_or_default().to_string(),
+            "author" => item::author(channel, item),
+            "date" => item::publication_date(item)
+                .map(|date| date.spoken())
+                .unwrap_or_default(),
+            "feed" => channel.title().to_string(),
+            _ => rest[start..=start + len].to_string(),
+        };
+        rendered.push_str(&value);
+        rest = &rest[start + len + 1..];
+    }
+    rendered.push_str(rest);
+
+    rendered
+}
@@ -1,3 +1,9 @@
+use std::{collections::BTreeMap, sync::Arc, time::Instant};
+
+use crate::{
+    audio::jingle::Jingles,
+    config::{Config, DigestPreset, FeedPreset, Secret},
+};
 
 #[derive(Clone)]
 pub struct Feed2PodcastURLs {
@@ -10,8 +16,60 @@ pub struct Feed2PodcastDirs {
     pub cache: String,
 }
 
+#[derive(Clone)]
+pub struct Feed2PodcastAdmin {
+    /// Token required to modify the cache (cache administration is disabled if not set)
+    pub token: Option<Secret>,
+}
+
 #[derive(Clone)]
 pub struct Feed2PodcastTTSConfig {
     pub model: String,
     pub voices: Option<Vec<String>>,
+    pub api_key: Option<Secret>,
+    pub stream: bool,
+}
+
+#[derive(Clone)]
+pub struct Feed2PodcastPresets {
+    pub feeds: BTreeMap<String, FeedPreset>,
+
+    /// Loaded jingles by preset name
+    pub jingles: BTreeMap<String, Arc<Jingles>>,
+
+    pub digests: BTreeMap<String, DigestPreset>,
+
+    /// Loaded jingles by digest name
+    pub digest_jingles: BTreeMap<String, Arc<Jingles>>,
+}
+
+impl Feed2PodcastPresets {
+    /// Preset of the given feed URL
+    pub fn for_feed(&self, url: &str) -> Option<&FeedPreset> {
+        self.feeds.values().find(|preset| preset.url == url)
+    }
+
+    /// Jingles of the preset of the given feed URL (none if there is no preset)
+    pub fn jingles_for_feed(&self, url: &str) -> Arc<Jingles> {
+        self.feeds
+            .iter()
+            .find(|(_, preset)| preset.url == url)
+            .and_then(|(name, _)| self.jingles.get(name).cloned())
+            .unwrap_or_default()
+    }
+
+    /// Digest with the given name and its jingles
+    pub fn digest(&self, name: &str) -> Option<(&DigestPreset, Arc<Jingles>)> {
+        let digest = self.digests.get(name)?;
+        Some((
+            digest,
+            self.digest_jingles.get(name).cloned().unwrap_or_default(),
+        ))
+    }
+}
+
+#[derive(Clone)]
+pub struct Feed2PodcastInfo {
+    pub started: Instant,
+    pub config: Arc<Config>,
 }
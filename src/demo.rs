@@ -1,78 +1,45 @@
 use std::path;
 
-use poem::{Error, Result, error::InternalServerError, web::Data};
-use poem_openapi::{
-    OpenApi,
-    param::Path,
-    payload::{Binary, Json},
-};
+use poem::{Error, Result, web::Data};
+use poem_openapi::{OpenApi, param::Path, payload::Json};
 use reqwest::StatusCode;
-use serde::Deserialize;
-use serde_json::json;
 
 use crate::{
-    cache,
+    cache::{self, AudioStream},
     data::{Feed2PodcastDirs, Feed2PodcastTTSConfig, Feed2PodcastURLs},
     schemas::{CategoryTags, DownloadFileResponse},
+    tts,
 };
 
-#[derive(Deserialize)]
-struct AvailableVoices {
-    voices: Vec<String>,
-}
+const DEMO_TEXT: &str = "The quick brown fox jumps over the lazy dog.";
 
 pub async fn generate_demo(
     file_path: &path::Path,
     voice: &str,
     tts_api_base: &str,
-    tts_model: &str,
-) -> Result<Vec<u8>> {
-    Ok(if !file_path.exists() {
-        let client = reqwest::Client::new();
-        let tts_req_body = json!({ "input": "The quick brown fox jumps over the lazy dog.", "model": tts_model, "voice": voice });
-        let podcast = client
-            .post(format!("{}/audio/speech", tts_api_base))
-            .body(tts_req_body.to_string())
-            .send()
-            .await
-            .map_err(|e| {
+    tts_conf: &Feed2PodcastTTSConfig,
+) -> Result<AudioStream> {
+    Ok(
+        if !tokio::fs::try_exists(file_path).await.unwrap_or(false) {
+            let podcast = tts::speech(DEMO_TEXT, voice, None, tts_api_base, tts_conf).await?;
+
+            tokio::fs::write(file_path, &podcast).await.map_err(|e| {
                 Error::from_string(
-                    format!("Unable to get response from TTS Server: {e}"),
+                    format!("Failed to write audio file: {e}"),
                     StatusCode::INTERNAL_SERVER_ERROR,
                 )
-            })?
-            .error_for_status()
-            .map_err(|e| {
+            })?;
+
+            podcast.into()
+        } else {
+            AudioStream::from_file(file_path).await.map_err(|e| {
                 Error::from_string(
-                    format!("Unable to get response from TTS Server: {e}"),
+                    format!("Failed to read audio file: {e}"),
                     StatusCode::INTERNAL_SERVER_ERROR,
                 )
             })?
-            .bytes()
-            .await
-            .map_err(|e| {
-                Error::from_string(
-                    format!("Failed to read TTS response body: {e}"),
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                )
-            })?;
-
-        std::fs::write(file_path, &podcast).map_err(|e| {
-            Error::from_string(
-                format!("Failed to write audio file: {e}"),
-                StatusCode::INTERNAL_SERVER_ERROR,
-            )
-        })?;
-
-        podcast.into()
-    } else {
-        std::fs::read(file_path).map_err(|e| {
-            Error::from_string(
-                format!("Failed to read audio file: {e}"),
-                StatusCode::INTERNAL_SERVER_ERROR,
-            )
-        })?
-    })
+        },
+    )
 }
 
 pub struct Router;
@@ -85,20 +52,7 @@ impl Router {
         Data(tts_conf): Data<&Feed2PodcastTTSConfig>,
         Data(app_urls): Data<&Feed2PodcastURLs>,
     ) -> Result<Json<Vec<String>>> {
-        let available_voices = match &tts_conf.voices {
-            Some(v) => v.clone(),
-            None => {
-                reqwest::get(&format!("{}/audio/voices", app_urls.tts))
-                    .await
-                    .map_err(InternalServerError)?
-                    .error_for_status()
-                    .map_err(InternalServerError)?
-                    .json::<AvailableVoices>()
-                    .await
-                    .map_err(InternalServerError)?
-                    .voices
-            }
-        };
+        let available_voices = tts::voices(tts_conf, app_urls).await?;
 
         Ok(Json(available_voices))
     }
@@ -115,11 +69,8 @@ impl Router {
     ) -> Result<DownloadFileResponse> {
         let audio_path = cache::get_demo_path(&app_dirs.cache, &tts_conf.model, &voice)?;
 
-        let audio = generate_demo(&audio_path, &voice, &app_urls.tts, &tts_conf.model).await?;
+        let audio = generate_demo(&audio_path, &voice, &app_urls.tts, tts_conf).await?;
 
-        Ok(DownloadFileResponse::Audio(
-            Binary(audio),
-            String::from("audio/mpeg"),
-        ))
+        Ok(DownloadFileResponse::mpeg(audio))
     }
 }
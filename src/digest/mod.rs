@@ -0,0 +1,210 @@
+mod period;
+pub use period::Period;
+
+use futures::future::join_all;
+use poem::{Error, Result};
+use reqwest::StatusCode;
+use rss::{Channel, Guid, Item};
+
+use crate::{
+    config::DigestPreset,
+    content::{Article, Script, publication_date, render_template},
+    feed::{channel_extension, fetch_channel},
+};
+
+/// Spoken before every article if the preset has no separator
+const DEFAULT_SEPARATOR: &str = "{title}.";
+
+/// Identifies a digest instead of a feed URL in cache keys, the cache index and transcript URLs
+pub fn source_id(name: &str) -> String {
+    format!("digest/{name}")
+}
+
+/// Item of one of the feeds of a digest
+struct DigestItem {
+    /// Publication day (days since 1970-01-01) and time
+    published: (i64, (u32, u32)),
+
+    /// Index of the feed
+    feed: usize,
+
+    item: Item,
+}
+
+/// Dated items of the feeds of a digest
+pub struct Digest {
+    channels: Vec<Channel>,
+
+    /// Oldest first
+    items: Vec<DigestItem>,
+}
+
+/// Fetch the feeds of a digest
+/// Feeds which can't be fetched are skipped (unless all of them fail), undated items are ignored
+pub async fn fetch(preset: &DigestPreset) -> Result<Digest> {
+    let mut channels = Vec::new();
+    let mut error = None;
+    for (url, channel) in preset
+        .feeds
+        .iter()
+        .zip(join_all(preset.feeds.iter().map(|url| fetch_channel(url))).await)
+    {
+        match channel {
+            Ok(channel) => channels.push(channel),
+            Err(e) => {
+                tracing::warn!("Skipping digest feed {url:?}: {e}");
+                error = Some(e);
+            }
+        }
+    }
+    if channels.is_empty() {
+        return Err(error.unwrap_or(Error::from_string(
+            "Digest has no feeds",
+            StatusCode::NOT_FOUND,
+        )));
+    }
+
+    let mut items: Vec<DigestItem> = channels
+        .iter()
+        .enumerate()
+        .flat_map(|(feed, channel)| {
+            channel.items().iter().filter_map(move |item| {
+                let date = publication_date(item)?;
+                Some(DigestItem {
+                    published: (date.days(), date.time.unwrap_or_default()),
+                    feed,
+                    item: item.clone(),
+                })
+            })
+        })
+        .collect();
+    items.sort_by_key(|item| item.published);
+
+    Ok(Digest { channels, items })
+}
+
+impl Digest {
+    /// Complete periods with items, newest first
+    pub fn periods(&self, preset: &DigestPreset) -> Vec<Period> {
+        let mut periods: Vec<Period> = self
+            .items
+            .iter()
+            .map(|item| Period::containing(preset.period, item.published.0))
+            .filter(Period::is_complete)
+            .collect();
+        periods.dedup();
+        periods.reverse();
+
+        periods
+    }
+
+    /// Items published in a period (oldest first) with their feed
+    fn items(&self, period: Period) -> impl Iterator<Item = (&Channel, &Item)> {
+        self.items
+            .iter()
+            .filter(move |item| period.contains(item.published.0))
+            .map(|item| (&self.channels[item.feed], &item.item))
+    }
+
+    /// Channel of the digest podcast (without items), derived from the first feed
+    pub fn channel(&self, preset: &DigestPreset) -> Channel {
+        let first = &self.channels[0];
+
+        let mut channel = Channel {
+            title: preset
+                .title
+                .clone()
+                .unwrap_or_else(|| first.title().to_string()),
+            link: first.link().to_string(),
+            description: format!(
+                "Digest of {}",
+                self.channels
+                    .iter()
+                    .map(Channel::title)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            language: first.language.clone(),
+            image: first.image.clone(),
+            itunes_ext: first.itunes_ext.clone(),
+            ..Default::default()
+        };
+        channel.set_itunes_ext(channel_extension(&channel, &preset.podcast));
+
+        channel
+    }
+
+    /// Episode of a period (without enclosure), its description lists the titles of the items
+    pub fn episode(&self, channel: &Channel, period: Period) -> Item {
+        Item {
+            title: Some(period.title()),
+            link: Some(channel.link().to_string()),
+            description: Some(
+                self.items(period)
+                    .filter_map(|(_, item)| item.title())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            guid: Some(Guid {
+                value: period.to_string(),
+                permalink: false,
+            }),
+            pub_date: Some(period.first_day().rfc2822()),
+            ..Default::default()
+        }
+    }
+
+    /// Number of characters of the titles and raw descriptions of the items in a period
+    pub fn characters(&self, period: Period) -> usize {
+        self.items(period)
+            .flat_map(|(_, item)| [item.title(), item.description()])
+            .flatten()
+            .map(|text| text.chars().count())
+            .sum()
+    }
+
+    /// Narrated text of the episode of a period, every item forms a chapter
+    pub fn script(
+        &self,
+        preset: &DigestPreset,
+        channel: &Channel,
+        episode: &Item,
+        period: Period,
+    ) -> Result<Script> {
+        let separator = preset.separator.as_deref().unwrap_or(DEFAULT_SEPARATOR);
+
+        let mut script = Script::default();
+        for (feed, item) in self.items(period) {
+            let article = match item.description() {
+                Some(article) => Article::parse(article, &preset.ignore)?.text,
+                None => String::new(),
+            };
+            let separator = render_template(separator, feed, item);
+
+            script.push_chapter(
+                item.title().unwrap_or(feed.title()),
+                &format!("{}\n\n{article}", separator.trim()),
+            );
+        }
+        if script.chunks.is_empty() {
+            return Err(Error::from_string(
+                format!("Digest has no items in period {period}"),
+                StatusCode::NOT_FOUND,
+            ));
+        }
+
+        let render = |template| {
+            render_template(template, channel, episode)
+                .trim()
+                .to_string()
+        };
+        if let Some(intro) = &preset.intro {
+            script.intro(&render(intro));
+        }
+        if let Some(outro) = &preset.outro {
+            script.outro(&render(outro));
+        }
+
+        Ok(script)
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{config::DigestPeriod, content::PublicationDate};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Days since 1970-01-01 (UTC)
+fn today() -> i64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    (now.as_secs() / SECONDS_PER_DAY) as i64
+}
+
+/// Time window of a digest episode, identified by its first day (e.g. `2024-06-10`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Period {
+    /// First day (days since 1970-01-01)
+    start: i64,
+
+    /// Length in days
+    days: i64,
+}
+
+impl Period {
+    /// Period of the given kind containing a day (days since 1970-01-01)
+    pub fn containing(kind: DigestPeriod, day: i64) -> Self {
+        match kind {
+            DigestPeriod::Daily => Self {
+                start: day,
+                days: 1,
+            },
+            // 1970-01-01 was a Thursday
+            DigestPeriod::Weekly => Self {
+                start: day - (day + 3).rem_euclid(7),
+                days: 7,
+            },
+        }
+    }
+
+    /// Parse the first day of a period (`YYYY-MM-DD`)
+    /// Returns `None` if the date is invalid or does not start a period of the given kind
+    pub fn parse(kind: DigestPeriod, value: &str) -> Option<Self> {
+        let mut parts = value.splitn(3, '-');
+        let date = PublicationDate {
+            year: parts.next()?.parse().ok()?,
+            month: parts
+                .next()?
+                .parse()
+                .ok()
+                .filter(|m| (1..=12).contains(m))?,
+            day: parts.next()?.parse().ok()?,
+            time: None,
+            offset: 0,
+        };
+
+        let period = Self::containing(kind, date.days());
+        (period.to_string() == value).then_some(period)
+    }
+
+    pub fn contains(&self, day: i64) -> bool {
+        (self.start..self.start + self.days).contains(&day)
+    }
+
+    /// Whether the period is over (items published later on belong to other periods)
+    pub fn is_complete(&self) -> bool {
+        self.start + self.days <= today()
+    }
+
+    pub fn first_day(&self) -> PublicationDate {
+        PublicationDate::from_days(self.start)
+    }
+
+    /// Title of the episode (e.g. `June 10, 2024` or `Week of June 10, 2024`)
+    pub fn title(&self) -> String {
+        match self.days {
+            1 => self.first_day().spoken(),
+            _ => format!("Week of {}", self.first_day().spoken()),
+        }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let date = self.first_day();
+        write!(f, "{:04}-{:02}-{:02}", date.year, date.month, date.day)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_containing_period() {
+        // 2024-06-13 is a Thursday, its week starts on Monday 2024-06-10
+        let daily = Period::containing(DigestPeriod::Daily, 19_887);
+        assert_eq!(daily.to_string(), "2024-06-13");
+        assert!(daily.contains(19_887) && !daily.contains(19_888));
+
+        let weekly = Period::containing(DigestPeriod::Weekly, 19_887);
+        assert_eq!(weekly.to_string(), "2024-06-10");
+        assert!(weekly.contains(19_884) && weekly.contains(19_890));
+        assert!(!weekly.contains(19_883) && !weekly.contains(19_891));
+        assert_eq!(Period::containing(DigestPeriod::Weekly, 19_884), weekly);
+
+        // Weeks before 1970-01-01 start on Mondays as well
+        assert_eq!(
+            Period::containing(DigestPeriod::Weekly, -1).to_string(),
+            "1969-12-29"
+        );
+    }
+
+    #[test]
+    fn parses_first_days() {
+        assert_eq!(
+            Period::parse(DigestPeriod::Daily, "2024-06-13"),
+            Some(Period::containing(DigestPeriod::Daily, 19_887))
+        );
+        assert_eq!(
+            Period::parse(DigestPeriod::Weekly, "2024-06-10"),
+            Some(Period::containing(DigestPeriod::Weekly, 19_887))
+        );
+
+        // Not the first day of a week
+        assert_eq!(Period::parse(DigestPeriod::Weekly, "2024-06-13"), None);
+
+        // Invalid or not normalized dates
+        for value in ["2024-02-30", "2024-13-01", "2024-6-10", "2024-06", "today"] {
+            assert_eq!(Period::parse(DigestPeriod::Daily, value), None, "{value}");
+        }
+    }
+
+    #[test]
+    fn titles_periods() {
+        assert_eq!(
+            Period::containing(DigestPeriod::Daily, 19_887).title(),
+            "June 13, 2024"
+        );
+        assert_eq!(
+            Period::containing(DigestPeriod::Weekly, 19_887).title(),
+            "Week of June 10, 2024"
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use poem::Result;
+
+use crate::{cache::CacheIndex, content::Article, lru::LruCache};
+
+/// Speaking rate used until episodes of a voice were generated (characters per second)
+const DEFAULT_CHARACTERS_PER_SECOND: f64 = 15.0;
+
+/// Bitrate used until episodes of a voice were generated (bytes per second, 128 kbps)
+const DEFAULT_BYTES_PER_SECOND: f64 = 16_000.0;
+
+/// Number of remembered article lengths
+pub const MAX_ARTICLE_LENGTHS: usize = 10_000;
+
+/// Narrated characters of parsed articles by hash of their content and ignored elements
+pub type ArticleLengths = LruCache<u64, usize>;
+
+/// Number of narrated characters of an article
+/// Lengths are remembered, so feed requests don't parse the same articles again
+pub fn article_length(lengths: &ArticleLengths, article: &str, ignore: &[String]) -> Result<usize> {
+    let mut hasher = DefaultHasher::new();
+    (article, ignore).hash(&mut hasher);
+    let hash = hasher.finish();
+
+    if let Some(length) = lengths.get(&hash) {
+        return Ok(length);
+    }
+
+    let length = Article::parse(article, ignore)?.text.chars().count();
+    lengths.insert(hash, length);
+
+    Ok(length)
+}
+
+/// Speaking rate and bitrate of a voice, measured using its generated episodes
+pub struct VoiceRate {
+    characters_per_second: f64,
+    bytes_per_second: f64,
+}
+
+impl VoiceRate {
+    /// Measure the rates of a voice (defaults are used if no episode of the voice is indexed)
+    /// The index keeps totals per voice, so this doesn't look at single episodes
+    pub fn measure(cache_index: &CacheIndex, voice: &str) -> Self {
+        match cache_index.voice_totals(voice) {
+            Some(totals) => Self {
+                characters_per_second: totals.characters as f64 / totals.seconds,
+                bytes_per_second: totals.bytes as f64 / totals.seconds,
+            },
+            None => Self {
+                characters_per_second: DEFAULT_CHARACTERS_PER_SECOND,
+                bytes_per_second: DEFAULT_BYTES_PER_SECOND,
+            },
+        }
+    }
+
+    /// Estimated duration (seconds) of audio with the given size
+    pub fn duration_of_size(&self, size: u64) -> f64 {
+        size as f64 / self.bytes_per_second
+    }
+
+    /// Estimated duration (seconds) and size (bytes) of narrating the given number of characters
+    pub fn estimate(&self, characters: usize) -> (f64, u64) {
+        let duration = characters as f64 / self.characters_per_second;
+
+        (duration, (duration * self.bytes_per_second) as u64)
+    }
+}
@@ -0,0 +1,37 @@
+use poem::{Error, Result};
+use reqwest::StatusCode;
+use rss::Channel;
+
+use crate::metrics;
+
+/// Fetch and parse the original RSS feed
+pub async fn fetch_channel(url: &str) -> Result<Channel> {
+    let channel = fetch(url).await;
+
+    metrics::FEED_FETCHES
+        .with_label_values(&[if channel.is_ok() { "success" } else { "error" }])
+        .inc();
+
+    channel
+}
+
+async fn fetch(url: &str) -> Result<Channel> {
+    let content = reqwest::get(url)
+        .await
+        .map_err(|e| {
+            Error::from_string(
+                format!("Unable to fetch original feed: {e}"),
+                StatusCode::BAD_REQUEST,
+            )
+        })?
+        .bytes()
+        .await
+        .map_err(|_| Error::from_string("Invalid feed content!", StatusCode::BAD_REQUEST))?;
+
+    Channel::read_from(&content[..]).map_err(|e| {
+        Error::from_string(
+            format!("Unable to parse feed: {e}"),
+            StatusCode::BAD_REQUEST,
+        )
+    })
+}
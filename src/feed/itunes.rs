@@ -0,0 +1,102 @@
+use rss::{
+    Channel, Item,
+    extension::itunes::{ITunesCategory, ITunesChannelExtension, ITunesItemExtension},
+};
+
+use crate::config::{PodcastMetadata, PodcastType};
+
+/// Category used if neither the feed nor the preset define one
+const DEFAULT_CATEGORY: &str = "News";
+
+/// Text content of HTML
+fn plain_text(html: &str) -> String {
+    scraper::Html::parse_fragment(html)
+        .root_element()
+        .text()
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Category with optional subcategories separated by `/` (e.g. `News/Tech News`)
+fn category(name: &str) -> ITunesCategory {
+    let mut names = name.rsplit('/').map(str::trim);
+    let mut category = ITunesCategory {
+        text: names.next().unwrap_or_default().to_string(),
+        subcategory: None,
+    };
+    for parent in names {
+        category = ITunesCategory {
+            text: parent.to_string(),
+            subcategory: Some(Box::new(category)),
+        };
+    }
+
+    category
+}
+
+/// Duration in the format `HH:MM:SS`
+fn format_duration(seconds: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60
+    )
+}
+
+/// Podcast metadata of the generated feed
+/// Values of the preset take precedence over values of the original feed, missing values are
+/// derived from the original feed (title, description, image)
+pub fn channel_extension(channel: &Channel, metadata: &PodcastMetadata) -> ITunesChannelExtension {
+    let mut ext = channel.itunes_ext.clone().unwrap_or_default();
+
+    ext.image = metadata
+        .image
+        .clone()
+        .or(ext.image)
+        .or(channel.image().map(|image| image.url().to_string()));
+    ext.author = metadata.author.clone().or(ext.author).or_else(|| {
+        channel
+            .dublin_core_ext()
+            .and_then(|dc| dc.creators().first().cloned())
+            .or(channel.managing_editor().map(String::from))
+            .or(Some(channel.title().to_string()))
+    });
+
+    if !metadata.categories.is_empty() {
+        ext.categories = metadata.categories.iter().map(|c| category(c)).collect();
+    } else if ext.categories.is_empty() {
+        ext.categories = vec![category(DEFAULT_CATEGORY)];
+    }
+
+    ext.explicit = metadata
+        .explicit
+        .map(|explicit| explicit.to_string())
+        .or(ext.explicit)
+        .or(Some(String::from("false")));
+    ext.r#type = metadata
+        .podcast_type
+        .map(|t| t.as_str().to_string())
+        .or(ext.r#type)
+        .or(Some(PodcastType::Episodic.as_str().to_string()));
+    ext.summary = metadata
+        .summary
+        .clone()
+        .or(ext.summary)
+        .or(Some(plain_text(channel.description())));
+
+    ext
+}
+
+/// Podcast metadata of an episode with the given duration (seconds)
+/// The episode number is only kept from the original item, counting the items of a feed would
+/// renumber the episodes whenever old items drop out of it
+pub fn item_extension(item: &Item, duration: f64) -> ITunesItemExtension {
+    let mut ext = item.itunes_ext.clone().unwrap_or_default();
+
+    ext.duration = Some(format_duration(duration.round() as u64));
+    ext.episode_type = ext.episode_type.or(Some(String::from("full")));
+
+    ext
+}
@@ -1,2 +1,10 @@
 mod routes;
 pub use routes::Router;
+
+mod fetch;
+pub use fetch::fetch_channel;
+
+mod estimate;
+pub use estimate::{ArticleLengths, MAX_ARTICLE_LENGTHS};
+mod itunes;
+pub use itunes::channel_extension;
@@ -1,3 +1,5 @@
+use std::{collections::BTreeMap, sync::Arc};
+
 use poem::{Error, Result, web::Data};
 use poem_openapi::{
     OpenApi,
@@ -5,10 +7,396 @@ use poem_openapi::{
     payload::PlainText,
 };
 use reqwest::StatusCode;
-use rss::{Channel, Enclosure, Item};
+use rss::{Enclosure, Item, extension::Extension};
 use url::Url;
 
-use crate::{data::Feed2PodcastURLs, schemas::CategoryTags};
+use crate::{
+    briefing,
+    cache::{self, CacheIndex},
+    config::{DigestPreset, FeedPreset},
+    content::render_template,
+    data::{Feed2PodcastPresets, Feed2PodcastURLs},
+    digest,
+    feed::{
+        estimate::{ArticleLengths, VoiceRate, article_length},
+        fetch_channel, itunes,
+    },
+    schemas::{CHAPTERS_MIME_TYPE, CategoryTags, TranscriptFormat},
+};
+
+/// Podcasting 2.0 namespace (used for transcripts and chapters)
+const PODCAST_NAMESPACE: &str = "https://podcastindex.org/namespace/1.0";
+
+/// `<podcast:transcript>` tags referencing the transcript of an episode in all formats
+fn transcript_tags(
+    app_urls: &Feed2PodcastURLs,
+    voice: &str,
+    url: &str,
+    uid: &str,
+) -> Result<Vec<Extension>> {
+    [
+        (TranscriptFormat::Vtt, "vtt"),
+        (TranscriptFormat::Srt, "srt"),
+        (TranscriptFormat::Text, "text"),
+    ]
+    .into_iter()
+    .map(|(format, name)| {
+        let transcript_url = Url::parse_with_params(
+            &format!("{}/api/transcript/{}", app_urls.base, voice),
+            [("url", url), ("uid", uid), ("format", name)],
+        )
+        .map_err(|e| {
+            Error::from_string(
+                format!("Unable to generate Transcript url for {}: {}", url, e),
+                StatusCode::BAD_REQUEST,
+            )
+        })?;
+        let mime_type = format.mime_type().split(';').next().unwrap_or_default();
+
+        Ok(Extension {
+            name: String::from("podcast:transcript"),
+            value: None,
+            attrs: BTreeMap::from([
+                (String::from("url"), transcript_url.to_string()),
+                (String::from("type"), String::from(mime_type)),
+            ]),
+            children: BTreeMap::new(),
+        })
+    })
+    .collect()
+}
+
+/// `<podcast:chapters>` tag referencing the chapters of an episode
+fn chapters_tag(
+    app_urls: &Feed2PodcastURLs,
+    voice: &str,
+    url: &str,
+    uid: &str,
+) -> Result<Extension> {
+    let chapters_url = Url::parse_with_params(
+        &format!("{}/api/transcript/{}/chapters", app_urls.base, voice),
+        [("url", url), ("uid", uid)],
+    )
+    .map_err(|e| {
+        Error::from_string(
+            format!("Unable to generate Chapters url for {}: {}", url, e),
+            StatusCode::BAD_REQUEST,
+        )
+    })?;
+
+    Ok(Extension {
+        name: String::from("podcast:chapters"),
+        value: None,
+        attrs: BTreeMap::from([
+            (String::from("url"), chapters_url.to_string()),
+            (String::from("type"), String::from(CHAPTERS_MIME_TYPE)),
+        ]),
+        children: BTreeMap::new(),
+    })
+}
+
+/// Generate a podcast feed from a regular RSS feed where the link to the audio points to the
+/// "Get Podcast Audio" endpoint
+/// Length and duration of episodes which were not generated yet are estimated
+async fn build_podcast_feed(
+    app_urls: &Feed2PodcastURLs,
+    cache_index: &CacheIndex,
+    article_lengths: &ArticleLengths,
+    voice: &str,
+    url: &str,
+    ignore: &[String],
+    normalize: bool,
+    preset: Option<&FeedPreset>,
+) -> Result<PlainText<String>> {
+    let channel = fetch_channel(url).await?;
+
+    let mut podcast_ch = channel.clone();
+    podcast_ch.set_itunes_ext(itunes::channel_extension(
+        &channel,
+        &preset
+            .map(|preset| preset.podcast.clone())
+            .unwrap_or_default(),
+    ));
+    podcast_ch
+        .namespaces
+        .insert(String::from("podcast"), String::from(PODCAST_NAMESPACE));
+
+    let rate = VoiceRate::measure(cache_index, voice);
+
+    podcast_ch.set_items(
+        channel
+            .items()
+            .iter()
+            .map(|item| {
+                let mut new_item = item.clone();
+
+                let uid = item
+                    .guid
+                    .clone()
+                    .ok_or(Error::from_string(
+                        "Unable to get GUID for some items",
+                        StatusCode::BAD_REQUEST,
+                    ))?
+                    .value;
+
+                let mut enclosure = Enclosure::default();
+                let mut url_params: Vec<(&str, String)> = ignore
+                    .iter()
+                    .cloned()
+                    .map(|i| ("ignore", i))
+                    .collect();
+                url_params.extend([
+                    ("url", String::from(url)),
+                    ("uid", uid.clone()),
+                    (
+                        "normalize",
+                        String::from(if normalize { "true" } else { "false" }),
+                    ),
+                ]);
+
+                enclosure.set_url(
+                    Url::parse_with_params(
+                        &format!("{}/api/content/{}", app_urls.base, voice),
+                        &url_params,
+                    )
+                    .map_err(|e| {
+                        Error::from_string(
+                            format!("Unable to generate Content url for {}: {}", url, e),
+                            StatusCode::BAD_REQUEST,
+                        )
+                    })?
+                    .as_str(),
+                );
+                enclosure.set_mime_type("audio/mpeg");
+
+                let key = cache::get_podcast_key(url, &uid, voice)?;
+                let entry = cache_index.get(&key);
+                if let Some(entry) = entry.as_ref().filter(|entry| entry.transcript) {
+                    let podcast_ext = new_item
+                        .extensions
+                        .entry(String::from("podcast"))
+                        .or_default();
+                    podcast_ext.insert(
+                        String::from("transcript"),
+                        transcript_tags(app_urls, voice, url, &uid)?,
+                    );
+                    if entry.chapters > 0 {
+                        podcast_ext.insert(
+                            String::from("chapters"),
+                            vec![chapters_tag(app_urls, voice, url, &uid)?],
+                        );
+                    }
+                }
+
+                let (duration, length) = match entry {
+                    Some(entry) => (
+                        entry
+                            .duration
+                            .unwrap_or_else(|| rate.duration_of_size(entry.size)),
+                        entry.size,
+                    ),
+                    None => {
+                        let mut characters = match item.description() {
+                            Some(article) => article_length(article_lengths, article, ignore)?,
+                            None => 0,
+                        };
+                        for template in preset
+                            .iter()
+                            .flat_map(|preset| [&preset.intro, &preset.outro])
+                            .flatten()
+                        {
+                            characters += render_template(template, &channel, item).chars().count();
+                        }
+                        rate.estimate(characters)
+                    }
+                };
+                enclosure.set_length(length.to_string());
+
+                new_item.set_enclosure(enclosure);
+                new_item.set_itunes_ext(itunes::item_extension(item, duration));
+                Ok(new_item)
+            })
+            .collect::<Result<Vec<Item>>>()?,
+    );
+
+    Ok(PlainText(podcast_ch.to_string()))
+}
+
+/// Generate a podcast feed with a single episode reading the headlines of the latest items of a
+/// regular RSS feed, the episode is replaced whenever the headlines change
+async fn build_briefing_feed(
+    app_urls: &Feed2PodcastURLs,
+    cache_index: &CacheIndex,
+    voice: &str,
+    url: &str,
+    ignore: &[String],
+    normalize: bool,
+    count: u32,
+    preset: Option<&FeedPreset>,
+) -> Result<PlainText<String>> {
+    let briefing = briefing::fetch(url, ignore, count).await?;
+    let source = briefing::source_id(url)?;
+    let uid = briefing.version.clone();
+
+    let mut podcast_ch = briefing.channel.clone();
+    podcast_ch.set_title(format!("{} (Headlines)", briefing.channel.title()));
+    podcast_ch.set_itunes_ext(itunes::channel_extension(
+        &briefing.channel,
+        &preset
+            .map(|preset| preset.podcast.clone())
+            .unwrap_or_default(),
+    ));
+    podcast_ch
+        .namespaces
+        .insert(String::from("podcast"), String::from(PODCAST_NAMESPACE));
+
+    let mut item = briefing.episode.clone();
+
+    let mut url_params: Vec<(&str, String)> =
+        ignore.iter().cloned().map(|i| ("ignore", i)).collect();
+    url_params.extend([
+        ("url", String::from(url)),
+        ("version", briefing.version.clone()),
+        ("count", count.to_string()),
+        (
+            "normalize",
+            String::from(if normalize { "true" } else { "false" }),
+        ),
+    ]);
+    let mut enclosure = Enclosure::default();
+    enclosure.set_url(
+        Url::parse_with_params(
+            &format!("{}/api/content/briefing/{}", app_urls.base, voice),
+            &url_params,
+        )
+        .map_err(|e| {
+            Error::from_string(
+                format!("Unable to generate Content url for {}: {}", url, e),
+                StatusCode::BAD_REQUEST,
+            )
+        })?
+        .as_str(),
+    );
+    enclosure.set_mime_type("audio/mpeg");
+
+    let key = cache::get_podcast_key(&source, &uid, voice)?;
+    let entry = cache_index.get(&key);
+    if let Some(entry) = entry.as_ref().filter(|entry| entry.transcript) {
+        let podcast_ext = item.extensions.entry(String::from("podcast")).or_default();
+        podcast_ext.insert(
+            String::from("transcript"),
+            transcript_tags(app_urls, voice, &source, &uid)?,
+        );
+        if entry.chapters > 0 {
+            podcast_ext.insert(
+                String::from("chapters"),
+                vec![chapters_tag(app_urls, voice, &source, &uid)?],
+            );
+        }
+    }
+
+    let rate = VoiceRate::measure(cache_index, voice);
+    let (duration, length) = match entry {
+        Some(entry) => (
+            entry
+                .duration
+                .unwrap_or_else(|| rate.duration_of_size(entry.size)),
+            entry.size,
+        ),
+        None => rate.estimate(briefing.script.text().chars().count()),
+    };
+    enclosure.set_length(length.to_string());
+
+    item.set_enclosure(enclosure);
+    item.set_itunes_ext(itunes::item_extension(&item, duration));
+    podcast_ch.set_items(vec![item]);
+
+    Ok(PlainText(podcast_ch.to_string()))
+}
+
+/// Generate the podcast feed of a digest, listing one episode per complete period with items
+/// Length and duration of episodes which were not generated yet are estimated
+async fn build_digest_feed(
+    app_urls: &Feed2PodcastURLs,
+    cache_index: &CacheIndex,
+    name: &str,
+    preset: &DigestPreset,
+) -> Result<PlainText<String>> {
+    let digest = digest::fetch(preset).await?;
+    let source = digest::source_id(name);
+
+    let mut podcast_ch = digest.channel(preset);
+    podcast_ch
+        .namespaces
+        .insert(String::from("podcast"), String::from(PODCAST_NAMESPACE));
+
+    let periods = digest.periods(preset);
+    let rate = VoiceRate::measure(cache_index, &preset.voice);
+
+    let mut items = Vec::new();
+    for period in periods.into_iter().take(preset.episodes as usize) {
+        let mut item = digest.episode(&podcast_ch, period);
+        let uid = period.to_string();
+
+        let audio_url = Url::parse_with_params(
+            &format!("{}/api/content/digest/{}", app_urls.base, name),
+            [("period", &uid)],
+        )
+        .map_err(|e| {
+            Error::from_string(
+                format!("Unable to generate Content url for digest {name}: {e}"),
+                StatusCode::BAD_REQUEST,
+            )
+        })?;
+        let mut enclosure = Enclosure::default();
+        enclosure.set_url(audio_url.as_str());
+        enclosure.set_mime_type("audio/mpeg");
+
+        let key = cache::get_podcast_key(&source, &uid, &preset.voice)?;
+        let entry = cache_index.get(&key);
+        if let Some(entry) = entry.as_ref().filter(|entry| entry.transcript) {
+            let podcast_ext = item.extensions.entry(String::from("podcast")).or_default();
+            podcast_ext.insert(
+                String::from("transcript"),
+                transcript_tags(app_urls, &preset.voice, &source, &uid)?,
+            );
+            if entry.chapters > 0 {
+                podcast_ext.insert(
+                    String::from("chapters"),
+                    vec![chapters_tag(app_urls, &preset.voice, &source, &uid)?],
+                );
+            }
+        }
+
+        let (duration, length) = match entry {
+            Some(entry) => (
+                entry
+                    .duration
+                    .unwrap_or_else(|| rate.duration_of_size(entry.size)),
+                entry.size,
+            ),
+            None => {
+                // A single article which cannot be parsed should not break the whole feed
+                let characters = match digest.script(preset, &podcast_ch, &item, period) {
+                    Ok(script) => script.text().chars().count(),
+                    Err(e) => {
+                        tracing::warn!("Estimating digest episode {uid} of {name}: {e}");
+                        digest.characters(period)
+                    }
+                };
+                rate.estimate(characters)
+            }
+        };
+        enclosure.set_length(length.to_string());
+
+        item.set_enclosure(enclosure);
+        item.set_itunes_ext(itunes::item_extension(&item, duration));
+        items.push(item);
+    }
+    podcast_ch.set_items(items);
+
+    Ok(PlainText(podcast_ch.to_string()))
+}
 
 pub struct Router;
 
@@ -20,6 +408,9 @@ impl Router {
     async fn podcast_feed(
         &self,
         Data(app_urls): Data<&Feed2PodcastURLs>,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+        Data(presets): Data<&Feed2PodcastPresets>,
+        Data(article_lengths): Data<&Arc<ArticleLengths>>,
 
         /// The voice to use for the podcast
         Path(voice): Path<String>,
@@ -32,82 +423,111 @@ impl Router {
         /// includes long numbers)
         Query(normalize): Query<bool>,
     ) -> Result<PlainText<String>> {
-        let ingore_unpacked = ignore.unwrap_or_default();
+        // Podcast metadata, intro and outro of a preset for the same feed apply as well
+        build_podcast_feed(
+            app_urls,
+            cache_index,
+            article_lengths,
+            &voice,
+            &url,
+            &ignore.unwrap_or_default(),
+            normalize,
+            presets.for_feed(&url),
+        )
+        .await
+    }
 
-        let content = reqwest::get(url.clone())
-            .await
-            .map_err(|e| {
-                Error::from_string(
-                    format!("Unable to fetch original feed: {e}"),
-                    StatusCode::BAD_REQUEST,
-                )
-            })?
-            .bytes()
-            .await
-            .map_err(|_| Error::from_string("Invalid feed content!", StatusCode::BAD_REQUEST))?;
-
-        let channel = Channel::read_from(&content[..]).map_err(|e| {
-            Error::from_string(
-                format!("Unable to parse feed: {e}"),
-                StatusCode::BAD_REQUEST,
-            )
-        })?;
+    /// Generate the podcast feed for a feed preset defined in the config file
+    #[oai(path = "/preset/:name", method = "get")]
+    async fn preset_podcast_feed(
+        &self,
+        Data(app_urls): Data<&Feed2PodcastURLs>,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+        Data(presets): Data<&Feed2PodcastPresets>,
+        Data(article_lengths): Data<&Arc<ArticleLengths>>,
+
+        /// The name of the preset
+        Path(name): Path<String>,
+    ) -> Result<PlainText<String>> {
+        let preset = presets.feeds.get(&name).ok_or(Error::from_string(
+            format!("Feed preset '{name}' does not exist!"),
+            StatusCode::NOT_FOUND,
+        ))?;
+
+        build_podcast_feed(
+            app_urls,
+            cache_index,
+            article_lengths,
+            &preset.voice,
+            &preset.url,
+            &preset.ignore,
+            preset.normalize,
+            Some(preset),
+        )
+        .await
+    }
 
-        let mut podcast_ch = channel.clone();
+    /// Generate a podcast feed with a single episode reading the title and first sentence of the
+    /// latest items of a regular RSS feed (regenerated when the feed changes)
+    #[oai(path = "/briefing/:voice", method = "get")]
+    async fn briefing_podcast_feed(
+        &self,
+        Data(app_urls): Data<&Feed2PodcastURLs>,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+        Data(presets): Data<&Feed2PodcastPresets>,
 
-        podcast_ch.set_items(
-            channel
-                .items()
-                .iter()
-                .map(|item| {
-                    let mut new_item = item.clone();
+        /// The voice to use for the podcast
+        Path(voice): Path<String>,
+        /// The Feed URL
+        Query(url): Query<String>,
+        /// Number of items to read (defaults to 10)
+        Query(count): Query<Option<u32>>,
+        /// HTML elements/CSS Selectors to ignore when parsing the content
+        Query(ignore): Query<Option<Vec<String>>>,
 
-                    let uid = item
-                        .guid
-                        .clone()
-                        .ok_or(Error::from_string(
-                            "Unable to get GUID for some items",
-                            StatusCode::BAD_REQUEST,
-                        ))?
-                        .value;
-
-                    let mut enclosure = Enclosure::default();
-                    let mut url_params: Vec<(&str, String)> = ingore_unpacked
-                        .clone()
-                        .into_iter()
-                        .map(|i| ("ignore", i))
-                        .collect();
-                    url_params.extend([
-                        ("url", url.clone()),
-                        ("uid", uid),
-                        (
-                            "normalize",
-                            String::from(if normalize { "true" } else { "false" }),
-                        ),
-                    ]);
-
-                    enclosure.set_url(
-                        Url::parse_with_params(
-                            &format!("{}/api/content/{}", app_urls.base, voice),
-                            &url_params,
-                        )
-                        .map_err(|e| {
-                            Error::from_string(
-                                format!("Unable to generate Content url for {}: {}", url, e),
-                                StatusCode::BAD_REQUEST,
-                            )
-                        })?
-                        .as_str(),
-                    );
-                    enclosure.set_mime_type("audio/mpeg");
+        /// Whether to normalize text for TTS (will improve TTS but can lead to errors when content
+        /// includes long numbers)
+        Query(normalize): Query<bool>,
+    ) -> Result<PlainText<String>> {
+        if count == Some(0) {
+            return Err(Error::from_string(
+                "count must be greater than 0",
+                StatusCode::BAD_REQUEST,
+            ));
+        }
 
-                    new_item.set_enclosure(enclosure);
-                    Ok(new_item)
-                })
-                .collect::<Result<Vec<Item>>>()?,
-        );
+        // Podcast metadata of a preset for the same feed applies as well
+        build_briefing_feed(
+            app_urls,
+            cache_index,
+            &voice,
+            &url,
+            &ignore.unwrap_or_default(),
+            normalize,
+            count.unwrap_or(briefing::DEFAULT_HEADLINES),
+            presets.for_feed(&url),
+        )
+        .await
+    }
+
+    /// Generate the podcast feed of a digest defined in the config file
+    /// Every episode combines the articles of the digest feeds published in one day or week
+    #[oai(path = "/digest/:name", method = "get")]
+    async fn digest_podcast_feed(
+        &self,
+        Data(app_urls): Data<&Feed2PodcastURLs>,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+        Data(presets): Data<&Feed2PodcastPresets>,
+
+        /// The name of the digest
+        Path(name): Path<String>,
+    ) -> Result<PlainText<String>> {
+        let preset = presets.digests.get(&name).ok_or(Error::from_string(
+            format!("Digest '{name}' does not exist!"),
+            StatusCode::NOT_FOUND,
+        ))?;
 
-        Ok(PlainText(podcast_ch.to_string()))
+        build_digest_feed(app_urls, cache_index, &name, preset).await
     }
 
     /// Helper endpoint to generate feed URL directly from the API docs
@@ -0,0 +1,145 @@
+use std::{path::Path, sync::Arc};
+
+use poem::{
+    IntoResponse, handler,
+    http::StatusCode,
+    web::{Data, Json},
+};
+use serde::Serialize;
+
+use crate::{
+    cache::AudioStorage,
+    data::{Feed2PodcastDirs, Feed2PodcastTTSConfig, Feed2PodcastURLs},
+    queue::GenerationQueue,
+    tts,
+};
+
+/// Result of a single readiness check
+#[derive(Serialize)]
+struct Check {
+    ok: bool,
+    detail: String,
+}
+
+impl Check {
+    fn new(result: std::result::Result<String, String>) -> Self {
+        match result {
+            Ok(detail) => Self { ok: true, detail },
+            Err(detail) => Self { ok: false, detail },
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Checks {
+    cache: Check,
+    storage: Check,
+    tts: Check,
+    queue: Check,
+}
+
+#[derive(Serialize)]
+struct Status<T: Serialize> {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checks: Option<T>,
+}
+
+/// Check whether the cache directory is writable (creates it if missing)
+async fn check_cache(cache_dir: &str) -> std::result::Result<String, String> {
+    let probe = Path::new(cache_dir).join(".readyz");
+
+    tokio::fs::create_dir_all(cache_dir)
+        .await
+        .map_err(|e| format!("Unable to create cache directory: {e}"))?;
+    tokio::fs::write(&probe, b"")
+        .await
+        .map_err(|e| format!("Cache directory is not writable: {e}"))?;
+    tokio::fs::remove_file(&probe)
+        .await
+        .map_err(|e| format!("Unable to remove probe file: {e}"))?;
+
+    Ok(String::from("Cache directory is writable"))
+}
+
+/// Check whether the audio storage is usable (see `AudioStorage::probe`)
+async fn check_storage(storage: &AudioStorage) -> std::result::Result<String, String> {
+    storage
+        .probe()
+        .await
+        .map(|_| format!("{} is available", storage.describe()))
+        .map_err(|e| format!("{} is not available: {e}", storage.describe()))
+}
+
+/// Check whether the TTS server answers on its voices endpoint
+async fn check_tts(
+    app_urls: &Feed2PodcastURLs,
+    tts_conf: &Feed2PodcastTTSConfig,
+) -> std::result::Result<String, String> {
+    tts::probe(&app_urls.tts, tts_conf)
+        .await
+        .map(|voices| format!("TTS server reachable ({} voices)", voices.len()))
+        .map_err(|e| format!("TTS server unreachable: {e}"))
+}
+
+/// Check whether the generation queue accepts more requests
+fn check_queue(queue: &GenerationQueue) -> std::result::Result<String, String> {
+    let detail = format!(
+        "{} waiting (max {}), {}/{} running",
+        queue.waiting(),
+        queue.max_waiting(),
+        queue.active(),
+        queue.parallel()
+    );
+
+    if queue.is_saturated() {
+        Err(format!("Generation queue saturated: {detail}"))
+    } else {
+        Ok(detail)
+    }
+}
+
+/// Liveness probe (the process is up and serving requests)
+#[handler]
+pub fn healthz() -> impl IntoResponse {
+    Json(Status::<Checks> {
+        status: "ok",
+        checks: None,
+    })
+}
+
+/// Readiness probe (cache directory is writable, audio storage is available, TTS server is
+/// reachable and generation queue is not saturated)
+#[handler]
+pub async fn readyz(
+    Data(app_urls): Data<&Feed2PodcastURLs>,
+    Data(app_dirs): Data<&Feed2PodcastDirs>,
+    Data(tts_conf): Data<&Feed2PodcastTTSConfig>,
+    Data(queue): Data<&Arc<GenerationQueue>>,
+    Data(storage): Data<&Arc<AudioStorage>>,
+) -> impl IntoResponse {
+    let (cache, storage, tts) = tokio::join!(
+        check_cache(&app_dirs.cache),
+        check_storage(storage),
+        check_tts(app_urls, tts_conf)
+    );
+    let checks = Checks {
+        cache: Check::new(cache),
+        storage: Check::new(storage),
+        tts: Check::new(tts),
+        queue: Check::new(check_queue(queue)),
+    };
+
+    let ready = checks.cache.ok && checks.storage.ok && checks.tts.ok && checks.queue.ok;
+    let (status, code) = if ready {
+        ("ok", StatusCode::OK)
+    } else {
+        ("error", StatusCode::SERVICE_UNAVAILABLE)
+    };
+
+    Json(Status {
+        status,
+        checks: Some(checks),
+    })
+    .with_status(code)
+}
@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use poem::{Result, error::InternalServerError, web::Data};
+use poem_openapi::{Object, OpenApi, payload::Json};
+
+use crate::{
+    cache::{AudioStorage, CacheIndex},
+    data::{Feed2PodcastInfo, Feed2PodcastTTSConfig, Feed2PodcastURLs},
+    queue::GenerationQueue,
+    schemas::CategoryTags,
+    tts,
+};
+
+/// State of the TTS backend
+#[derive(Object)]
+pub struct TtsInfo {
+    /// URL of the TTS API
+    pub url: String,
+    /// TTS model in use
+    pub model: String,
+    /// Available voices (configured or reported by the TTS server)
+    pub voices: Vec<String>,
+    /// Whether the TTS server answered on its voices endpoint
+    pub reachable: bool,
+    /// Error returned while contacting the TTS server
+    pub error: Option<String>,
+}
+
+/// Cache usage
+#[derive(Object)]
+pub struct CacheInfo {
+    /// Storage of podcast audio
+    pub storage: String,
+    /// Size of cached podcasts in bytes (without voice demos)
+    pub used: u64,
+    /// Number of cached podcast episodes
+    pub episodes: usize,
+    /// Size of pinned podcasts in bytes (not counted towards the limit)
+    pub pinned: u64,
+    /// Maximum cache size in bytes
+    pub limit: Option<u64>,
+}
+
+/// State of the podcast generation queue
+#[derive(Object)]
+pub struct QueueInfo {
+    /// Requests waiting for a generation slot
+    pub waiting: usize,
+    /// Generations currently running
+    pub active: usize,
+    /// Maximum number of parallel generations
+    pub parallel: usize,
+    /// Number of waiting requests after which the server reports not ready
+    pub max_waiting: usize,
+}
+
+/// Diagnostic information about the server
+#[derive(Object)]
+pub struct ServerInfo {
+    /// Version of feed2podcast
+    pub version: String,
+    /// Time since server start in seconds
+    pub uptime: u64,
+    /// Effective configuration (secrets are redacted)
+    pub config: serde_json::Value,
+    pub tts: TtsInfo,
+    pub cache: CacheInfo,
+    pub queue: QueueInfo,
+}
+
+/// Collect diagnostic information about the server
+pub async fn get_server_info(
+    info: &Feed2PodcastInfo,
+    tts_conf: &Feed2PodcastTTSConfig,
+    app_urls: &Feed2PodcastURLs,
+    cache_index: &CacheIndex,
+    storage: &AudioStorage,
+    queue: &GenerationQueue,
+) -> Result<ServerInfo> {
+    let (reachable, server_voices, error) = match tts::probe(&app_urls.tts, tts_conf).await {
+        Ok(voices) => (true, voices, None),
+        Err(e) => (false, Vec::new(), Some(e)),
+    };
+    let pins = cache_index.pins();
+
+    Ok(ServerInfo {
+        version: String::from(env!("CARGO_PKG_VERSION")),
+        uptime: info.started.elapsed().as_secs(),
+        config: serde_json::to_value(info.config.as_ref()).map_err(InternalServerError)?,
+        tts: TtsInfo {
+            url: app_urls.tts.clone(),
+            model: tts_conf.model.clone(),
+            voices: tts_conf.voices.clone().unwrap_or(server_voices),
+            reachable,
+            error,
+        },
+        cache: CacheInfo {
+            storage: storage.describe(),
+            used: cache_index.size(),
+            episodes: cache_index.len(),
+            pinned: cache_index
+                .entries()
+                .iter()
+                .filter(|entry| pins.covers(entry))
+                .map(|entry| entry.stored_size())
+                .sum(),
+            limit: info
+                .config
+                .cache
+                .max_size
+                .map(|sz| (sz as u64) * (1e9 as u64)),
+        },
+        queue: QueueInfo {
+            waiting: queue.waiting(),
+            active: queue.active(),
+            parallel: queue.parallel(),
+            max_waiting: queue.max_waiting(),
+        },
+    })
+}
+
+pub struct Router;
+
+#[OpenApi(prefix_path = "info", tag = "CategoryTags::Info")]
+impl Router {
+    /// Get version, effective configuration and state of the server for diagnostics
+    #[oai(path = "/", method = "get")]
+    async fn get_info(
+        &self,
+        Data(info): Data<&Feed2PodcastInfo>,
+        Data(tts_conf): Data<&Feed2PodcastTTSConfig>,
+        Data(app_urls): Data<&Feed2PodcastURLs>,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+        Data(storage): Data<&Arc<AudioStorage>>,
+        Data(queue): Data<&Arc<GenerationQueue>>,
+    ) -> Result<Json<ServerInfo>> {
+        Ok(Json(
+            get_server_info(info, tts_conf, app_urls, cache_index, storage, queue).await?,
+        ))
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    sync::{Mutex, MutexGuard},
+};
+
+/// Map of a limited number of values shared by requests (in memory)
+/// The least recently used value is removed once the capacity is exceeded
+pub struct LruCache<K, V> {
+    capacity: usize,
+    state: Mutex<State<K, V>>,
+}
+
+struct State<K, V> {
+    /// Values with the time of their last use
+    values: HashMap<K, (u64, V)>,
+
+    /// Keys by the time of their last use
+    used: BTreeMap<u64, K>,
+
+    /// Time of the last use (counting uses)
+    clock: u64,
+}
+
+impl<K: Clone + Eq + Hash, V: Clone> LruCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            state: Mutex::new(State {
+                values: HashMap::new(),
+                used: BTreeMap::new(),
+                clock: 0,
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<K, V>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Value of a key (marking it as used)
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let mut state = self.lock();
+        let state = &mut *state;
+        state.clock += 1;
+
+        let (used, value) = state.values.get_mut(key)?;
+        let key = state.used.remove(used)?;
+        *used = state.clock;
+        state.used.insert(state.clock, key);
+
+        Some(value.clone())
+    }
+
+    /// Add or replace the value of a key, removes the least recently used value if the capacity
+    /// is exceeded
+    pub fn insert(&self, key: K, value: V) {
+        let mut state = self.lock();
+        state.clock += 1;
+
+        let clock = state.clock;
+        if let Some((used, _)) = state.values.insert(key.clone(), (clock, value)) {
+            state.used.remove(&used);
+        }
+        state.used.insert(clock, key);
+
+        while state.values.len() > self.capacity {
+            let Some((_, key)) = state.used.pop_first() else {
+                break;
+            };
+            state.values.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_least_recently_used_values() {
+        let cache = LruCache::new(2);
+        cache.insert(String::from("a"), 1);
+        cache.insert(String::from("b"), 2);
+        assert_eq!(cache.get("a"), Some(1));
+
+        cache.insert(String::from("c"), 3);
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.get("c"), Some(3));
+
+        // Replacing a value uses it
+        cache.insert(String::from("a"), 4);
+        cache.insert(String::from("d"), 5);
+        assert_eq!(cache.get("c"), None);
+        assert_eq!(cache.get("a"), Some(4));
+        assert_eq!(cache.get("d"), Some(5));
+    }
+}
@@ -1,4 +1,7 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use poem::{
     EndpointExt, Route,
@@ -8,115 +11,67 @@ use poem::{
 use poem_openapi::OpenApiService;
 
 use clap::Parser;
-use eyre::{Result, eyre};
+use eyre::{Context, Result, eyre};
 
+mod audio;
 mod content;
 mod demo;
 mod feed;
+mod info;
 mod webui;
 
+mod briefing;
 mod cache;
+mod config;
 mod data;
+mod digest;
+mod health;
+mod lru;
+mod metrics;
+mod queue;
 mod schemas;
+mod transcript;
+mod tts;
 use data::Feed2PodcastURLs;
-use tokio::sync::Semaphore;
 use tracing_subscriber::EnvFilter;
 
-use crate::data::{Feed2PodcastDirs, Feed2PodcastTTSConfig};
-
-#[derive(Parser, Debug)]
-#[command(name = "feed2podcast")]
-#[command(
-    version,
-    about = "Generate podcast feed from text based rss feeds using TTS"
-)]
-struct Args {
-    /// URL to the API (make sure to change this when changing the port and using the API docs).
-    #[arg(
-        short,
-        long,
-        help = "URL to the API (make sure to change this when changing the port and using the API docs)",
-        env = "FEED2PODCAST_URL",
-        default_value_t = String::from("http://127.0.0.1:3000")
-    )]
-    url: String,
-
-    /// The port on which the server listens for requests.
-    #[arg(
-        short,
-        long,
-        help = "The port on which the server listens for requests.",
-        env = "FEED2PODCAST_PORT",
-        default_value_t = 3000
-    )]
-    port: u16,
-
-    /// Disable the SwaggerUI API docs (/docs).
-    #[arg(
-        short,
-        long,
-        help = "Disable the SwaggerUI API docs (/docs)",
-        env = "FEED2PODCAST_DISABLE_DOCS",
-        default_value_t = false
-    )]
-    disable_docs: bool,
-
-    /// Cache directory for podcast files
-    #[arg(
-        short,
-        long,
-        help = "Cache directory for podcast files",
-        env = "FEED2PODCAST_CACHE_DIR",
-        default_value_t = String::from("./cache")
-    )]
-    cache_dir: String,
-
-    /// URL to a OpenAI compatible TTS API.
-    #[arg(
-        short,
-        long,
-        help = "URL to a OpenAI compatible TTS API.
-",
-        env = "FEED2PODCAST_TTS_API",
-        default_value = "http://127.0.0.1:5000/v1"
-    )]
-    tts_url: String,
-
-    /// Available Voices for TTS (uses audio/voices if not set)
-    #[arg(
-        short,
-        long,
-        help = "Comma separated list of available Voices for TTS (uses audio/voices if not set)",
-        env = "FEED2PODCAST_VOICES",
-        value_delimiter = ','
-    )]
-    voices: Vec<String>,
-
-    /// TTS Model to use
-    #[arg(
-        short,
-        long,
-        help = "TTS Model to use (Defaults to 'kokoro')",
-        env = "FEED2PODCAST_MODEL",
-        default_value = "kokoro"
-    )]
-    model: String,
-
-    /// Max cache size
-    #[arg(
-        long,
-        help = "Maximum cache size GB (Only one of --cache-size and --cache-age is taken into account, If both are provided --cache-size is used)",
-        env = "FEED2PODCAST_MAX_CACHE_SIZE"
-    )]
-    cache_size: Option<u32>,
-
-    /// Max cache age
-    #[arg(
-        long,
-        help = "Maximum cache age in days (cleanup only runs when new podcast is generated)",
-        env = "FEED2PODCAST_MAX_CACHE_AGE"
-    )]
-    cache_age: Option<u32>,
+use crate::{
+    audio::{jingle::Jingles, process::PostProcessing},
+    cache::{AudioStorage, CacheIndex},
+    config::{Args, Command, Config},
+    content::{Artworks, MAX_ARTWORKS},
+    data::{
+        Feed2PodcastAdmin, Feed2PodcastDirs, Feed2PodcastInfo, Feed2PodcastPresets,
+        Feed2PodcastTTSConfig,
+    },
+    feed::{ArticleLengths, MAX_ARTICLE_LENGTHS},
+    queue::GenerationQueue,
+};
+
+/// Time open connections (e.g. streamed downloads) get to finish after a shutdown signal
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wait for Ctrl+C or SIGTERM
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Unable to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
 }
 
 #[tokio::main]
@@ -129,32 +84,103 @@ async fn main() -> Result<()> {
         )))
         .init();
 
-    let args = Args::parse();
+    let started = Instant::now();
+    let mut args = Args::parse();
+    let command = args.command.take();
+    let config = Config::load(args)?;
 
-    tracing::info!("Starting server with config: {args:#?}");
+    let storage = Arc::new(match &config.cache.s3 {
+        Some(s3) => AudioStorage::s3(s3)?,
+        None => AudioStorage::local(&config.cache.dir),
+    });
 
+    if let Some(Command::RebuildIndex) = command {
+        let index = CacheIndex::rebuild(&config.cache.dir, &storage).await?;
+        tracing::info!(
+            "Rebuilt cache index with {} episodes ({} bytes)",
+            index.len(),
+            index.size()
+        );
+        return Ok(());
+    }
 
-    let cache_cleanup_method = if let Some(max_sz) = args.cache_size {
-        cache::CleanupMethod::MaxStorage((max_sz as u64) * (1e9 as u64))
-    } else if let Some(max_days) = args.cache_age {
-        cache::CleanupMethod::MaxAge(Duration::from_secs((max_days as u64) * 24 * 60 * 60))
-    } else {
-        cache::CleanupMethod::None
-    };
+    tracing::info!("Starting server with config: {config:#?}");
+
+    let cache_index = Arc::new(
+        CacheIndex::open(&config.cache.dir, &storage)
+            .await?
+            .with_pinned_feeds(config.cache.pinned_feeds.clone()),
+    );
+
+    // Accesses are kept in memory and written to the index log periodically
+    tokio::spawn(cache::run_flush_task(
+        cache_index.clone(),
+        Duration::from_secs(60),
+    ));
+
+    metrics::init();
+
+    let jingles = config
+        .feeds
+        .iter()
+        .map(|(name, preset)| {
+            Jingles::load(&preset.jingles)
+                .wrap_err_with(|| format!("Invalid jingles of feed preset '{name}'"))
+                .map(|jingles| (name.clone(), Arc::new(jingles)))
+        })
+        .collect::<Result<_>>()?;
+    let digest_jingles = config
+        .digests
+        .iter()
+        .map(|(name, digest)| {
+            Jingles::load(&digest.jingles)
+                .wrap_err_with(|| format!("Invalid jingles of digest '{name}'"))
+                .map(|jingles| (name.clone(), Arc::new(jingles)))
+        })
+        .collect::<Result<_>>()?;
+
+    let cache_cleanup_policy = cache::CleanupPolicy::new(
+        config.cache.max_size.map(|sz| (sz as u64) * (1e9 as u64)),
+        config
+            .cache
+            .max_age
+            .map(|days| Duration::from_secs((days as u64) * 24 * 60 * 60)),
+        config
+            .cache
+            .min_free_space
+            .map(|sz| (sz as u64) * (1e9 as u64)),
+        config.feed_quotas(),
+    );
+
+    if config.cache.maintenance_interval > 0 {
+        tokio::spawn(cache::run_maintenance_task(
+            cache_index.clone(),
+            storage.clone(),
+            cache_cleanup_policy.clone(),
+            Duration::from_secs((config.cache.maintenance_interval as u64) * 60),
+        ));
+    }
 
     // The number of allowed parallel podcast generations
     // WARNING: Currently more than 1 could cause issues where one podcast is generated multiple
     // times
-    let podcast_generation_permit = Arc::new(Semaphore::new(1));
+    let generation_queue = Arc::new(GenerationQueue::new(1, config.server.max_queue));
 
     // Create an OpenAPI service with the provided API and server URL.
     let api_service = OpenApiService::new(
-        (feed::Router, content::Router, demo::Router),
+        (
+            feed::Router,
+            content::Router,
+            demo::Router,
+            info::Router,
+            cache::Router,
+            transcript::Router,
+        ),
         "feed2podcast",
         "0.1.0",
     )
     .description("Generate podcast feed from text based rss feeds using TTS\n\n[WebUI](/)")
-    .server(args.url.clone())
+    .server(config.server.url.clone())
     .url_prefix("/api");
 
     let webui_service = OpenApiService::new(webui::Router, "feed2podcast", "0.1.0");
@@ -162,37 +188,74 @@ async fn main() -> Result<()> {
     // Generate SwaggerUI documentation for the API.
     let docs = api_service.swagger_ui();
 
-    let mut server = Route::new().nest("api", api_service);
-    if !args.disable_docs {
+    let mut server = Route::new()
+        .at("/healthz", health::healthz)
+        .at("/readyz", health::readyz)
+        .at("/metrics", metrics::metrics)
+        .nest("api", api_service);
+    if !config.server.disable_docs {
         server = server.nest("docs", docs);
     }
 
     server = server.nest("/", webui_service);
 
+    let server_info = Feed2PodcastInfo {
+        started,
+        config: Arc::new(config.clone()),
+    };
+
+    // Recorded accesses are written to the index once the server stopped
+    let flushed_index = cache_index.clone();
+
     // Start the server with CORS middleware enabled.
-    poem::Server::new(TcpListener::bind(format!("0.0.0.0:{}", args.port)))
-        .run(
+    poem::Server::new(TcpListener::bind(format!("0.0.0.0:{}", config.server.port)))
+        .run_with_graceful_shutdown(
             server
                 .with(Cors::new())
                 .with(Tracing)
                 .data(Feed2PodcastURLs {
-                    base: args.url,
-                    tts: args.tts_url,
+                    base: config.server.url,
+                    tts: config.tts.url,
                 })
                 .data(Feed2PodcastDirs {
-                    cache: args.cache_dir,
+                    cache: config.cache.dir,
+                })
+                .data(Feed2PodcastAdmin {
+                    token: config.server.admin_token,
                 })
                 .data(Feed2PodcastTTSConfig {
-                    model: args.model,
-                    voices: if args.voices.is_empty() {
-                        None
-                    } else {
-                        Some(args.voices)
-                    },
+                    model: config.tts.model,
+                    voices: config.tts.voices,
+                    api_key: config.tts.api_key,
+                    stream: config.tts.stream,
+                })
+                .data(PostProcessing {
+                    loudness: config.audio.loudness,
+                    trim_silence: config.audio.trim_silence,
+                })
+                .data(Feed2PodcastPresets {
+                    feeds: config.feeds,
+                    jingles,
+                    digests: config.digests,
+                    digest_jingles,
                 })
-                .data(podcast_generation_permit)
-                .data(cache_cleanup_method),
+                .data(Arc::new(ArticleLengths::new(MAX_ARTICLE_LENGTHS)))
+                .data(Arc::new(Artworks::new(MAX_ARTWORKS)))
+                .data(generation_queue)
+                .data(server_info)
+                .data(cache_cleanup_policy)
+                .data(cache_index)
+                .data(storage),
+            shutdown_signal(),
+            Some(SHUTDOWN_TIMEOUT),
         )
         .await
-        .map_err(|e| eyre!(format!("Server failed with error: {e}")))
+        .map_err(|e| eyre!(format!("Server failed with error: {e}")))?;
+
+    if let Err(e) = flushed_index.flush() {
+        tracing::error!("{e}");
+    }
+    flushed_index.sync().await;
+
+    Ok(())
 }
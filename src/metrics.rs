@@ -0,0 +1,138 @@
+use std::sync::{Arc, LazyLock};
+
+use poem::{
+    Error, IntoResponse, Result, handler,
+    http::StatusCode,
+    web::{Data, WithContentType},
+};
+use prometheus::{
+    Encoder, HistogramVec, IntCounter, IntCounterVec, IntGauge, TextEncoder, histogram_opts,
+    opts, register_histogram_vec, register_int_counter, register_int_counter_vec,
+    register_int_gauge,
+};
+
+use crate::queue::GenerationQueue;
+
+/// Fetched source feeds by result (`success`, `error`)
+pub static FEED_FETCHES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        opts!("feed2podcast_feed_fetches_total", "Fetched source feeds"),
+        &["result"]
+    )
+    .expect("Failed to register metric")
+});
+
+/// Podcast audio requests by cache result (`hit`, `miss`)
+pub static PODCAST_CACHE: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        opts!(
+            "feed2podcast_podcast_cache_requests_total",
+            "Podcast audio requests by cache result"
+        ),
+        &["result"]
+    )
+    .expect("Failed to register metric")
+});
+
+/// Duration of TTS requests by model and voice
+pub static TTS_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        histogram_opts!(
+            "feed2podcast_tts_duration_seconds",
+            "Duration of TTS requests",
+            vec![0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0]
+        ),
+        &["model", "voice"]
+    )
+    .expect("Failed to register metric")
+});
+
+/// Synthesized characters by model and voice
+pub static TTS_CHARACTERS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        opts!(
+            "feed2podcast_tts_characters_total",
+            "Characters synthesized by the TTS server"
+        ),
+        &["model", "voice"]
+    )
+    .expect("Failed to register metric")
+});
+
+/// Completed cache cleanup runs
+pub static CLEANUP_RUNS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        opts!("feed2podcast_cleanup_runs_total", "Cache cleanup runs"),
+        &["result"]
+    )
+    .expect("Failed to register metric")
+});
+
+/// Bytes freed by cache cleanup
+pub static CLEANUP_FREED_BYTES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "feed2podcast_cleanup_freed_bytes_total",
+        "Bytes freed by cache cleanup"
+    )
+    .expect("Failed to register metric")
+});
+
+static QUEUE_WAITING: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "feed2podcast_queue_waiting",
+        "Requests waiting for a podcast generation slot"
+    )
+    .expect("Failed to register metric")
+});
+
+static QUEUE_ACTIVE: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "feed2podcast_queue_active",
+        "Podcast generation permits in use"
+    )
+    .expect("Failed to register metric")
+});
+
+static QUEUE_PARALLEL: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "feed2podcast_queue_permits",
+        "Maximum number of parallel podcast generations"
+    )
+    .expect("Failed to register metric")
+});
+
+/// Register all metrics (so they are reported before first use)
+pub fn init() {
+    LazyLock::force(&FEED_FETCHES);
+    LazyLock::force(&PODCAST_CACHE);
+    LazyLock::force(&TTS_DURATION);
+    LazyLock::force(&TTS_CHARACTERS);
+    LazyLock::force(&CLEANUP_RUNS);
+    LazyLock::force(&CLEANUP_FREED_BYTES);
+    LazyLock::force(&QUEUE_WAITING);
+    LazyLock::force(&QUEUE_ACTIVE);
+    LazyLock::force(&QUEUE_PARALLEL);
+}
+
+/// Metrics in Prometheus text format
+#[handler]
+pub fn metrics(
+    Data(queue): Data<&Arc<GenerationQueue>>,
+) -> Result<WithContentType<Vec<u8>>> {
+    QUEUE_WAITING.set(queue.waiting() as i64);
+    QUEUE_ACTIVE.set(queue.active() as i64);
+    QUEUE_PARALLEL.set(queue.parallel() as i64);
+
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    encoder
+        .encode(&prometheus::gather(), &mut buffer)
+        .map_err(|e| {
+            Error::from_string(
+                format!("Failed to encode metrics: {e}"),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        })?;
+
+    Ok(buffer.with_content_type(encoder.format_type().to_string()))
+}
@@ -0,0 +1,75 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use poem::{Error, Result};
+use reqwest::StatusCode;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Queue limiting the number of parallel podcast generations
+pub struct GenerationQueue {
+    permits: Arc<Semaphore>,
+    parallel: usize,
+    max_waiting: usize,
+    waiting: AtomicUsize,
+}
+
+/// Decrements the waiting counter when dropped (also if the request is cancelled)
+struct WaitingGuard<'a>(&'a AtomicUsize);
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl GenerationQueue {
+    pub fn new(parallel: usize, max_waiting: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(parallel)),
+            parallel,
+            max_waiting,
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    /// Wait for a free generation slot
+    /// The slot is released when the permit is dropped (it can be moved into a background task)
+    pub async fn acquire(&self) -> Result<OwnedSemaphorePermit> {
+        self.waiting.fetch_add(1, Ordering::Relaxed);
+        let _guard = WaitingGuard(&self.waiting);
+
+        self.permits.clone().acquire_owned().await.map_err(|e| {
+            Error::from_string(
+                format!("Failed to acquire permit for podcast generation: {e}"),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        })
+    }
+
+    /// Number of requests waiting for a generation slot
+    pub fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::Relaxed)
+    }
+
+    /// Number of generations currently running
+    pub fn active(&self) -> usize {
+        self.parallel - self.permits.available_permits()
+    }
+
+    /// Maximum number of parallel generations
+    pub fn parallel(&self) -> usize {
+        self.parallel
+    }
+
+    /// Number of waiting requests after which the queue is considered saturated
+    pub fn max_waiting(&self) -> usize {
+        self.max_waiting
+    }
+
+    /// Whether the queue is saturated (too many waiting requests)
+    pub fn is_saturated(&self) -> bool {
+        self.waiting() >= self.max_waiting
+    }
+}
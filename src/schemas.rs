@@ -1,4 +1,7 @@
-use poem_openapi::{ApiResponse, Tags, payload::Binary};
+use poem::Body;
+use poem_openapi::{ApiResponse, Enum, Tags, payload::Binary};
+
+use crate::cache::AudioStream;
 
 /// OpenAPI Category Tags for API endpoints
 #[derive(Tags)]
@@ -9,12 +12,128 @@ pub enum CategoryTags {
     /// Voice demos and similar
     Demo,
 
+    /// Cache administration
+    Cache,
+
+    /// Server information and diagnostics
+    Info,
+
     /// WebUI pages
     WebUI
 }
 
 #[derive(Debug, ApiResponse)]
 pub enum DownloadFileResponse {
+    /// Audio which is still being generated has no known length
+    #[oai(status = 200)]
+    Audio(
+        Binary<Body>,
+        #[oai(header = "content-type")] String,
+        #[oai(header = "content-length")] Option<u64>,
+        #[oai(header = "accept-ranges")] Option<String>,
+        #[oai(header = "etag")] Option<String>,
+        #[oai(header = "last-modified")] Option<String>,
+    ),
+
+    /// Requested range of the audio
+    #[oai(status = 206)]
+    PartialAudio(
+        Binary<Body>,
+        #[oai(header = "content-type")] String,
+        #[oai(header = "content-length")] u64,
+        #[oai(header = "content-range")] String,
+        #[oai(header = "accept-ranges")] String,
+        #[oai(header = "etag")] Option<String>,
+        #[oai(header = "last-modified")] Option<String>,
+    ),
+
+    /// Audio is available from the audio storage directly (presigned URL)
+    #[oai(status = 307)]
+    Redirect(#[oai(header = "location")] String),
+
+    /// The copy of the client is still valid
+    #[oai(status = 304)]
+    NotModified(
+        #[oai(header = "etag")] Option<String>,
+        #[oai(header = "last-modified")] Option<String>,
+    ),
+
+    /// The requested range lies outside of the audio
+    #[oai(status = 416)]
+    RangeNotSatisfiable(#[oai(header = "content-range")] String),
+}
+
+impl DownloadFileResponse {
+    /// Stream MP3 audio to the client
+    pub fn mpeg(audio: AudioStream) -> Self {
+        Self::Audio(
+            Binary(audio.body),
+            String::from("audio/mpeg"),
+            Some(audio.size),
+            None,
+            None,
+            None,
+        )
+    }
+}
+
+/// Response to a `HEAD` request for audio (nothing is generated)
+#[derive(Debug, ApiResponse)]
+pub enum AudioHeadResponse {
+    /// Size and validators are only known if the audio was generated already
+    #[oai(status = 200)]
+    Audio(
+        #[oai(header = "content-type")] String,
+        #[oai(header = "content-length")] Option<u64>,
+        #[oai(header = "accept-ranges")] String,
+        #[oai(header = "etag")] Option<String>,
+        #[oai(header = "last-modified")] Option<String>,
+    ),
+
+    /// The copy of the client is still valid
+    #[oai(status = 304)]
+    NotModified(
+        #[oai(header = "etag")] Option<String>,
+        #[oai(header = "last-modified")] Option<String>,
+    ),
+}
+
+/// Format of a transcript
+#[derive(Debug, Clone, Copy, Default, Enum)]
+#[oai(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    /// Plain text without timestamps
+    #[default]
+    Text,
+
+    /// WebVTT
+    Vtt,
+
+    /// SubRip
+    Srt,
+}
+
+impl TranscriptFormat {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Text => "text/plain; charset=utf-8",
+            Self::Vtt => "text/vtt; charset=utf-8",
+            Self::Srt => "application/srt; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Debug, ApiResponse)]
+pub enum TranscriptResponse {
+    #[oai(status = 200)]
+    Transcript(Binary<Vec<u8>>, #[oai(header = "content-type")] String),
+}
+
+/// MIME type of Podcasting 2.0 JSON chapters
+pub const CHAPTERS_MIME_TYPE: &str = "application/json+chapters";
+
+#[derive(Debug, ApiResponse)]
+pub enum ChaptersResponse {
     #[oai(status = 200)]
-    Audio(Binary<Vec<u8>>, #[oai(header = "content-type")] String),
+    Chapters(Binary<Vec<u8>>, #[oai(header = "content-type")] String),
 }
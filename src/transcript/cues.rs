@@ -0,0 +1,310 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Maximum length of a text chunk which is synthesized at once (in characters)
+const MAX_CHUNK_CHARS: usize = 500;
+
+/// Part of the narrated text with the time it is spoken
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Cue {
+    /// Start in seconds
+    pub start: f64,
+
+    /// End in seconds
+    pub end: f64,
+
+    /// Narrated text (including surrounding whitespace)
+    pub text: String,
+}
+
+/// Chapter of an episode
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Chapter {
+    /// Start in seconds
+    pub start: f64,
+
+    pub title: String,
+}
+
+/// Narrated text of an episode
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Transcript {
+    pub cues: Vec<Cue>,
+
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
+}
+
+/// Escape text of a WebVTT cue, so it is neither read as markup nor as a timing line (`-->`)
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Timestamp in the format `HH:MM:SS<separator>mmm`
+fn timestamp(seconds: f64, separator: char) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+
+    format!(
+        "{:02}:{:02}:{:02}{separator}{:03}",
+        millis / 3_600_000,
+        (millis / 60_000) % 60,
+        (millis / 1000) % 60,
+        millis % 1000
+    )
+}
+
+impl Transcript {
+    /// Add the text spoken between the end of the last cue and `end`
+    pub fn push(&mut self, text: &str, end: f64) {
+        let start = self.cues.last().map_or(0.0, |cue| cue.end);
+
+        self.cues.push(Cue {
+            start,
+            end,
+            text: String::from(text),
+        });
+    }
+
+    /// Add audio without narrated text (e.g. jingles) with the given duration
+    pub fn push_audio(&mut self, duration: f64) {
+        let end = self.cues.last().map_or(0.0, |cue| cue.end) + duration;
+        self.push("", end);
+    }
+
+    /// Start a chapter with the next cue
+    pub fn start_chapter(&mut self, title: &str) {
+        self.chapters.push(Chapter {
+            start: self.cues.last().map_or(0.0, |cue| cue.end),
+            title: String::from(title),
+        });
+    }
+
+    /// Chapters with their end in seconds (start of the next chapter or end of the last cue)
+    pub fn chapter_ranges(&self) -> Vec<(&Chapter, f64)> {
+        let end = self.cues.last().map_or(0.0, |cue| cue.end);
+
+        self.chapters
+            .iter()
+            .enumerate()
+            .map(|(nr, chapter)| {
+                let next = self.chapters.get(nr + 1).map_or(end, |next| next.start);
+                (chapter, next)
+            })
+            .collect()
+    }
+
+    /// Chapters in the Podcasting 2.0 JSON chapters format
+    pub fn chapters_json(&self) -> serde_json::Value {
+        json!({
+            "version": "1.2.0",
+            "chapters": self
+                .chapters
+                .iter()
+                .map(|chapter| json!({
+                    "startTime": (chapter.start * 1000.0).round() / 1000.0,
+                    "title": chapter.title,
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    /// Cues with text on a single line (cues without text are skipped)
+    fn lines(&self) -> impl Iterator<Item = (&Cue, String)> {
+        self.cues.iter().filter_map(|cue| {
+            let line = cue.text.split_whitespace().collect::<Vec<_>>().join(" ");
+            (!line.is_empty()).then_some((cue, line))
+        })
+    }
+
+    /// Narrated text without timestamps
+    pub fn text(&self) -> String {
+        self.cues
+            .iter()
+            .map(|cue| cue.text.as_str())
+            .collect::<String>()
+            .trim()
+            .to_string()
+    }
+
+    /// Transcript in the WebVTT format
+    pub fn vtt(&self) -> String {
+        let mut vtt = String::from("WEBVTT\n");
+        for (cue, line) in self.lines() {
+            vtt += &format!(
+                "\n{} --> {}\n{}\n",
+                timestamp(cue.start, '.'),
+                timestamp(cue.end, '.'),
+                escape_vtt(&line)
+            );
+        }
+
+        vtt
+    }
+
+    /// Transcript in the SubRip format
+    pub fn srt(&self) -> String {
+        let mut srt = String::new();
+        for (nr, (cue, line)) in self.lines().enumerate() {
+            srt += &format!(
+                "{}\n{} --> {}\n{line}\n\n",
+                nr + 1,
+                timestamp(cue.start, ','),
+                timestamp(cue.end, ',')
+            );
+        }
+
+        srt
+    }
+}
+
+/// Split text into sentences (whitespace following a sentence belongs to it)
+pub fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut after_punctuation = false;
+    let mut boundary = false;
+
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            boundary |= after_punctuation || c == '\n';
+            after_punctuation = false;
+            continue;
+        }
+
+        if boundary {
+            sentences.push(&text[start..i]);
+            start = i;
+            boundary = false;
+        }
+        after_punctuation = matches!(c, '.' | '!' | '?' | '…');
+    }
+
+    if start < text.len() {
+        sentences.push(&text[start..]);
+    }
+
+    sentences
+}
+
+/// Split narrated text into chunks which are synthesized separately (one cue per chunk)
+/// Chunks end at sentence boundaries if possible, joining them results in the original text
+pub fn split_text(text: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for sentence in sentences(text) {
+        // Overlong sentences are split between words
+        let pieces: Vec<&str> = if sentence.chars().count() > MAX_CHUNK_CHARS {
+            sentence.split_inclusive(char::is_whitespace).collect()
+        } else {
+            vec![sentence]
+        };
+
+        for piece in pieces {
+            if !current.trim().is_empty()
+                && current.chars().count() + piece.chars().count() > MAX_CHUNK_CHARS
+            {
+                chunks.push(std::mem::take(&mut current));
+            }
+            current.push_str(piece);
+        }
+    }
+
+    match chunks.last_mut() {
+        Some(last) if current.trim().is_empty() => last.push_str(&current),
+        _ => chunks.push(current),
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_sentences() {
+        assert_eq!(
+            sentences("First one. Second one!  Third?\nLast line\nNext"),
+            [
+                "First one. ",
+                "Second one!  ",
+                "Third?\n",
+                "Last line\n",
+                "Next"
+            ]
+        );
+        assert_eq!(sentences("Version 1.5 is out."), ["Version 1.5 is out."]);
+        assert_eq!(sentences("Wait… what?"), ["Wait… ", "what?"]);
+        assert!(sentences("").is_empty());
+    }
+
+    #[test]
+    fn splits_text_into_chunks() {
+        let sentence = "This sentence has exactly fifty characters in it. ";
+        let text = sentence.repeat(25);
+
+        let chunks = split_text(&text);
+        assert_eq!(chunks.len(), 3);
+        assert!(
+            chunks
+                .iter()
+                .all(|chunk| chunk.chars().count() <= MAX_CHUNK_CHARS)
+        );
+        assert!(chunks.iter().all(|chunk| chunk.ends_with(". ")));
+        assert_eq!(chunks.concat(), text);
+
+        // Overlong sentences are split between words
+        let text = "word ".repeat(250);
+        let chunks = split_text(&text);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|chunk| chunk.ends_with(' ')));
+        assert_eq!(chunks.concat(), text);
+
+        // Trailing whitespace stays with the last chunk
+        assert_eq!(split_text("Short. \n\n"), ["Short. \n\n"]);
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(timestamp(0.0, '.'), "00:00:00.000");
+        assert_eq!(timestamp(-1.0, '.'), "00:00:00.000");
+        assert_eq!(timestamp(61.2345, '.'), "00:01:01.235");
+        assert_eq!(timestamp(3725.5, ','), "01:02:05,500");
+        assert_eq!(timestamp(59.9996, ','), "00:01:00,000");
+    }
+
+    #[test]
+    fn formats_subtitles() {
+        let mut transcript = Transcript::default();
+        transcript.push_audio(1.5);
+        transcript.push("Hello   there.\n", 3.25);
+        transcript.push("Bye.", 4.0);
+
+        assert_eq!(
+            transcript.vtt(),
+            "WEBVTT\n\n00:00:01.500 --> 00:00:03.250\nHello there.\n\n\
+             00:00:03.250 --> 00:00:04.000\nBye.\n"
+        );
+        assert_eq!(
+            transcript.srt(),
+            "1\n00:00:01,500 --> 00:00:03,250\nHello there.\n\n\
+             2\n00:00:03,250 --> 00:00:04,000\nBye.\n\n"
+        );
+        assert_eq!(transcript.text(), "Hello   there.\nBye.");
+    }
+
+    #[test]
+    fn escapes_cue_text() {
+        let mut transcript = Transcript::default();
+        transcript.push("If a < b & b > c, then a --> c.", 2.0);
+
+        assert_eq!(
+            transcript.vtt(),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:02.000\n\
+             If a &lt; b &amp; b &gt; c, then a --&gt; c.\n"
+        );
+    }
+}
@@ -0,0 +1,5 @@
+mod routes;
+pub use routes::Router;
+
+mod cues;
+pub use cues::{Transcript, sentences, split_text};
@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use poem::{Error, Result, web::Data};
+use poem_openapi::{
+    OpenApi,
+    param::{Path, Query},
+    payload::Binary,
+};
+use reqwest::StatusCode;
+
+use crate::{
+    cache::{self, AudioStorage},
+    schemas::{
+        CHAPTERS_MIME_TYPE, CategoryTags, ChaptersResponse, TranscriptFormat, TranscriptResponse,
+    },
+    transcript::Transcript,
+};
+
+/// Read the stored transcript of an episode
+async fn load_transcript(
+    storage: &AudioStorage,
+    url: &str,
+    uid: &str,
+    voice: &str,
+) -> Result<Transcript> {
+    let key = cache::get_transcript_key(&cache::get_podcast_key(url, uid, voice)?);
+
+    let stored = storage
+        .open(&key, None)
+        .await
+        .map_err(|e| Error::from_string(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or(Error::from_string(
+            "No transcript available, the episode was not generated yet",
+            StatusCode::NOT_FOUND,
+        ))?;
+    let content = stored.body.into_bytes().await.map_err(|e| {
+        Error::from_string(
+            format!("Failed to read transcript: {e}"),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    })?;
+
+    serde_json::from_slice(&content).map_err(|e| {
+        Error::from_string(
+            format!("Invalid transcript: {e}"),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    })
+}
+
+pub struct Router;
+
+#[OpenApi(prefix_path = "transcript", tag = "CategoryTags::Feed")]
+impl Router {
+    /// Transcript of the Podcast audio for a given article
+    /// Transcripts are available once the audio was generated
+    #[oai(path = "/:voice", method = "get")]
+    async fn get_transcript(
+        &self,
+        Data(storage): Data<&Arc<AudioStorage>>,
+
+        /// The voice of the podcast
+        Path(voice): Path<String>,
+
+        /// The Feed URL
+        Query(url): Query<String>,
+
+        /// The GUID of the article
+        Query(uid): Query<String>,
+
+        /// Format of the transcript (defaults to plain text)
+        Query(format): Query<Option<TranscriptFormat>>,
+    ) -> Result<TranscriptResponse> {
+        let transcript = load_transcript(storage, &url, &uid, &voice).await?;
+
+        let format = format.unwrap_or_default();
+        let text = match format {
+            TranscriptFormat::Text => transcript.text(),
+            TranscriptFormat::Vtt => transcript.vtt(),
+            TranscriptFormat::Srt => transcript.srt(),
+        };
+
+        Ok(TranscriptResponse::Transcript(
+            Binary(text.into_bytes()),
+            String::from(format.mime_type()),
+        ))
+    }
+
+    /// Chapters of the Podcast audio for a given article (Podcasting 2.0 JSON chapters)
+    /// Chapters are derived from the headings of the article once the audio was generated
+    #[oai(path = "/:voice/chapters", method = "get")]
+    async fn get_chapters(
+        &self,
+        Data(storage): Data<&Arc<AudioStorage>>,
+
+        /// The voice of the podcast
+        Path(voice): Path<String>,
+
+        /// The Feed URL
+        Query(url): Query<String>,
+
+        /// The GUID of the article
+        Query(uid): Query<String>,
+    ) -> Result<ChaptersResponse> {
+        let transcript = load_transcript(storage, &url, &uid, &voice).await?;
+
+        Ok(ChaptersResponse::Chapters(
+            Binary(transcript.chapters_json().to_string().into_bytes()),
+            String::from(CHAPTERS_MIME_TYPE),
+        ))
+    }
+}
@@ -0,0 +1,208 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+use poem::{Error, Result, error::InternalServerError};
+use prometheus::HistogramTimer;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    data::{Feed2PodcastTTSConfig, Feed2PodcastURLs},
+    metrics,
+};
+
+/// Timeout when checking whether the TTS server is reachable
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize)]
+struct AvailableVoices {
+    voices: Vec<String>,
+}
+
+/// Add authentication to a request for the TTS API (if configured)
+fn authenticate(request: RequestBuilder, tts_conf: &Feed2PodcastTTSConfig) -> RequestBuilder {
+    match &tts_conf.api_key {
+        Some(key) => request.bearer_auth(key.expose()),
+        None => request,
+    }
+}
+
+/// Send a speech request to the TTS API (the audio is not read yet)
+async fn request_speech(
+    input: &str,
+    voice: &str,
+    normalize: Option<bool>,
+    stream: bool,
+    tts_api_base: &str,
+    tts_conf: &Feed2PodcastTTSConfig,
+) -> Result<Response> {
+    let mut tts_req_body = json!({ "input": input, "model": tts_conf.model, "voice": voice });
+    if stream {
+        tts_req_body["stream"] = json!(true);
+    }
+    if let Some(normalize) = normalize {
+        tts_req_body["normalization_options"] = json!({ "normalize": normalize });
+    }
+
+    let client = reqwest::Client::new();
+    authenticate(
+        client.post(format!("{}/audio/speech", tts_api_base)),
+        tts_conf,
+    )
+    .body(tts_req_body.to_string())
+    .send()
+    .await
+    .map_err(|e| {
+        Error::from_string(
+            format!("Unable to get response from TTS Server: {e}"),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    })?
+    .error_for_status()
+    .map_err(|e| {
+        Error::from_string(
+            format!("Unable to get response from TTS Server: {e}"),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    })
+}
+
+/// Record the metrics of a completed speech generation
+fn observe_speech(
+    input: &str,
+    voice: &str,
+    tts_conf: &Feed2PodcastTTSConfig,
+    timer: HistogramTimer,
+) {
+    timer.observe_duration();
+    metrics::TTS_CHARACTERS
+        .with_label_values(&[tts_conf.model.as_str(), voice])
+        .inc_by(input.chars().count() as u64);
+}
+
+/// Generate speech for the given text using the TTS API
+pub async fn speech(
+    input: &str,
+    voice: &str,
+    normalize: Option<bool>,
+    tts_api_base: &str,
+    tts_conf: &Feed2PodcastTTSConfig,
+) -> Result<Bytes> {
+    let timer = metrics::TTS_DURATION
+        .with_label_values(&[tts_conf.model.as_str(), voice])
+        .start_timer();
+
+    let audio = request_speech(input, voice, normalize, false, tts_api_base, tts_conf)
+        .await?
+        .bytes()
+        .await
+        .map_err(|e| {
+            Error::from_string(
+                format!("Failed to read TTS response body: {e}"),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        })?;
+
+    observe_speech(input, voice, tts_conf, timer);
+
+    Ok(audio)
+}
+
+/// Speech which is received from the TTS API while it is synthesized
+pub struct SpeechStream {
+    response: Response,
+    input: String,
+    voice: String,
+    tts_conf: Feed2PodcastTTSConfig,
+
+    /// Observed once the speech is complete
+    timer: Option<HistogramTimer>,
+}
+
+impl SpeechStream {
+    /// Next received chunk of audio (`None` once the speech is complete)
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>> {
+        let chunk = self.response.chunk().await.map_err(|e| {
+            Error::from_string(
+                format!("Failed to read TTS response body: {e}"),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        })?;
+
+        if chunk.is_none()
+            && let Some(timer) = self.timer.take()
+        {
+            observe_speech(&self.input, &self.voice, &self.tts_conf, timer);
+        }
+
+        Ok(chunk)
+    }
+}
+
+/// Generate speech for the given text using the streaming mode of the TTS API
+/// Fails early if the TTS server rejects the request, the audio is read using `SpeechStream`
+pub async fn speech_stream(
+    input: &str,
+    voice: &str,
+    normalize: Option<bool>,
+    tts_api_base: &str,
+    tts_conf: &Feed2PodcastTTSConfig,
+) -> Result<SpeechStream> {
+    let timer = metrics::TTS_DURATION
+        .with_label_values(&[tts_conf.model.as_str(), voice])
+        .start_timer();
+
+    let response = request_speech(input, voice, normalize, true, tts_api_base, tts_conf).await?;
+
+    Ok(SpeechStream {
+        response,
+        input: String::from(input),
+        voice: String::from(voice),
+        tts_conf: tts_conf.clone(),
+        timer: Some(timer),
+    })
+}
+
+/// Get voices available on the TTS server (audio/voices)
+pub async fn server_voices(
+    tts_api_base: &str,
+    tts_conf: &Feed2PodcastTTSConfig,
+) -> reqwest::Result<Vec<String>> {
+    let client = reqwest::Client::new();
+    Ok(authenticate(
+        client.get(format!("{}/audio/voices", tts_api_base)),
+        tts_conf,
+    )
+    .send()
+    .await?
+    .error_for_status()?
+    .json::<AvailableVoices>()
+    .await?
+    .voices)
+}
+
+/// Check whether the TTS server is reachable on its voices endpoint (returns reported voices)
+pub async fn probe(
+    tts_api_base: &str,
+    tts_conf: &Feed2PodcastTTSConfig,
+) -> std::result::Result<Vec<String>, String> {
+    match tokio::time::timeout(PROBE_TIMEOUT, server_voices(tts_api_base, tts_conf)).await {
+        Ok(Ok(voices)) => Ok(voices),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(String::from("Request timed out")),
+    }
+}
+
+/// Get available voices (configured voices or audio/voices of the TTS API)
+pub async fn voices(
+    tts_conf: &Feed2PodcastTTSConfig,
+    app_urls: &Feed2PodcastURLs,
+) -> Result<Vec<String>> {
+    Ok(match &tts_conf.voices {
+        Some(v) => v.clone(),
+        None => server_voices(&app_urls.tts, tts_conf)
+            .await
+            .map_err(InternalServerError)?,
+    })
+}
@@ -1,41 +1,48 @@
+use std::sync::Arc;
+
 use askama::Template;
 use poem::Result;
 use poem::error::InternalServerError;
 use poem::web::Data;
 use poem_openapi::{OpenApi, payload::Html};
-use serde::Deserialize;
 
-use crate::data::{Feed2PodcastTTSConfig, Feed2PodcastURLs};
+use crate::cache::{AudioStorage, CacheIndex};
+use crate::data::{Feed2PodcastInfo, Feed2PodcastTTSConfig, Feed2PodcastURLs};
+use crate::info::get_server_info;
+use crate::queue::GenerationQueue;
 use crate::schemas::CategoryTags;
+use crate::tts;
 use crate::webui::templates;
 
-pub struct Router;
+/// Format size in bytes for humans
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
 
-#[derive(Deserialize)]
-struct AvailableVoices {
-    voices: Vec<String>,
+    format!("{size:.1} {}", UNITS[unit])
 }
 
-async fn get_voices(
-    tts_conf: &Feed2PodcastTTSConfig,
-    app_urls: &Feed2PodcastURLs,
-) -> Result<Vec<String>> {
-    Ok(match &tts_conf.voices {
-        Some(v) => v.clone(),
-        None => {
-            reqwest::get(&format!("{}/audio/voices", app_urls.tts))
-                .await
-                .map_err(InternalServerError)?
-                .error_for_status()
-                .map_err(InternalServerError)?
-                .json::<AvailableVoices>()
-                .await
-                .map_err(InternalServerError)?
-                .voices
-        }
-    })
+/// Format duration in seconds for humans
+fn format_uptime(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86400, (secs % 86400) / 3600, (secs % 3600) / 60);
+
+    if days > 0 {
+        format!("{days}d {hours}h {minutes}m")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else {
+        format!("{minutes}m {}s", secs % 60)
+    }
 }
 
+pub struct Router;
+
 #[OpenApi(tag = "CategoryTags::WebUI")]
 impl Router {
     #[oai(path = "/", method = "get")]
@@ -45,7 +52,7 @@ impl Router {
         Data(tts_conf): Data<&Feed2PodcastTTSConfig>,
         Data(app_urls): Data<&Feed2PodcastURLs>,
     ) -> Result<Html<String>> {
-        let available_voices = get_voices(tts_conf, app_urls).await?;
+        let available_voices = tts::voices(tts_conf, app_urls).await?;
 
         Ok(Html(
             templates::Index {
@@ -66,7 +73,7 @@ impl Router {
         Data(tts_conf): Data<&Feed2PodcastTTSConfig>,
         Data(app_urls): Data<&Feed2PodcastURLs>,
     ) -> Result<Html<String>> {
-        let available_voices = get_voices(tts_conf, app_urls).await?;
+        let available_voices = tts::voices(tts_conf, app_urls).await?;
 
         Ok(Html(
             templates::Demo {
@@ -78,4 +85,35 @@ impl Router {
             .map_err(InternalServerError)?,
         ))
     }
+
+    #[oai(path = "/about", method = "get")]
+    async fn about(
+        &self,
+
+        Data(info): Data<&Feed2PodcastInfo>,
+        Data(tts_conf): Data<&Feed2PodcastTTSConfig>,
+        Data(app_urls): Data<&Feed2PodcastURLs>,
+        Data(cache_index): Data<&Arc<CacheIndex>>,
+        Data(storage): Data<&Arc<AudioStorage>>,
+        Data(queue): Data<&Arc<GenerationQueue>>,
+    ) -> Result<Html<String>> {
+        let server_info =
+            get_server_info(info, tts_conf, app_urls, cache_index, storage, queue).await?;
+
+        Ok(Html(
+            templates::About {
+                title: "Feed2Podcast About",
+                description: "Version and state of the Feed2Podcast server.",
+                uptime: format_uptime(server_info.uptime),
+                cache_used: format_bytes(server_info.cache.used),
+                cache_limit: server_info.cache.limit.map(format_bytes),
+                cache_pinned: format_bytes(server_info.cache.pinned),
+                config: serde_json::to_string_pretty(&server_info.config)
+                    .map_err(InternalServerError)?,
+                info: server_info,
+            }
+            .render()
+            .map_err(InternalServerError)?,
+        ))
+    }
 }
@@ -1,5 +1,7 @@
 use askama::Template;
 
+use crate::info::ServerInfo;
+
 #[allow(dead_code)]
 #[derive(Template)]
 #[template(path = "index.html")]
@@ -18,3 +20,17 @@ pub struct Demo<'a> {
     pub(super) description: &'a str,
     pub(super) voices: Vec<String>
 }
+
+#[allow(dead_code)]
+#[derive(Template)]
+#[template(path = "about.html")]
+pub struct About<'a> {
+    pub(super) title: &'a str,
+    pub(super) description: &'a str,
+    pub(super) info: ServerInfo,
+    pub(super) uptime: String,
+    pub(super) cache_used: String,
+    pub(super) cache_limit: Option<String>,
+    pub(super) cache_pinned: String,
+    pub(super) config: String,
+}